@@ -1,17 +1,23 @@
 //! A simple counter example demonstrating evcore's event-driven architecture.
 //!
 //! This example implements a basic counter that can be incremented or decremented
-//! via commands. It uses in-memory mock implementations for all backends.
+//! via commands. It uses in-memory mock implementations for the stream and inbox,
+//! and a lock file for leader election.
 //!
-//! Run with: `cargo run --example counter`
+//! Run with: `cargo run --example counter -- [lock-file]`
+//!
+//! The lock file defaults to `$EVCORE_COUNTER_LOCK`, or to a file in the
+//! system temp directory unique to the process.
 
+use evcore::file::FileElection;
 use evcore::logic::Logic;
-use evcore::sequencer::EventGenerator;
-use evcore::{Election, Inbox, Producer, Receiver, Sender, Sequencer, Stream};
+use evcore::sequencer::{CatchUp, EventGenerator, SequencerBuilder};
+use evcore::{Inbox, Producer, Receiver, Sender, Sequencer, Stream};
 
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex, mpsc};
-use std::thread;
 use std::time::Duration;
+use std::{env, process, thread};
 
 /// Shared subscriber registry for broadcast semantics.
 type Subscribers = Arc<Mutex<Vec<mpsc::Sender<Vec<u8>>>>>;
@@ -113,20 +119,6 @@ impl Sender for MemorySender {
     }
 }
 
-/// Always-wins election for single-node demonstration.
-struct AlwaysLeader;
-
-impl Election for AlwaysLeader {
-    fn elect(&self) -> bool {
-        println!("[election] acquired leadership");
-        true
-    }
-
-    fn renew(&self) -> bool {
-        true
-    }
-}
-
 /// Commands that can be sent to the counter.
 #[derive(Debug)]
 enum Command {
@@ -274,6 +266,15 @@ impl Sequencer for CounterLogic {
     }
 }
 
+/// Returns the lock file named on the command line or in the environment.
+fn lock_path() -> PathBuf {
+    env::args_os()
+        .nth(1)
+        .or_else(|| env::var_os("EVCORE_COUNTER_LOCK"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::temp_dir().join(format!("evcore-counter-{}.lock", process::id())))
+}
+
 fn main() {
     println!("evcore counter example");
     println!("======================");
@@ -291,7 +292,9 @@ fn main() {
         rx: Mutex::new(inbox_rx),
         tx: Mutex::new(inbox_tx),
     };
-    let election = AlwaysLeader;
+    let lock = lock_path();
+    println!("[main] electing with lock file {}", lock.display());
+    let election = FileElection::new(&lock, Duration::from_secs(1)).unwrap();
 
     thread::scope(|s| {
         // Spawn a consumer thread using evcore::consumer::run
@@ -313,7 +316,7 @@ fn main() {
                     .duration_since(SystemTime::UNIX_EPOCH)
                     .unwrap()
                     .subsec_nanos();
-                if nanos % 2 == 0 {
+                if nanos.is_multiple_of(2) {
                     sender.send(b"inc");
                 } else {
                     sender.send(b"dec");
@@ -325,14 +328,10 @@ fn main() {
         // Run the sequencer (this blocks forever in a real application)
         println!("[main] starting sequencer...");
         let sequencer = CounterLogic::new("sequencer");
-        evcore::sequencer::run(
-            &stream,
-            &producer,
-            &inbox,
-            &election,
-            sequencer,
-            Duration::from_millis(100),
-            Duration::from_secs(1),
-        );
+        SequencerBuilder::new()
+            .renew_interval(Duration::from_millis(100))
+            .catch_up(CatchUp::LogicOrIdle(Duration::from_secs(1)))
+            .run(&stream, &producer, &inbox, &election, sequencer)
+            .expect("sequencer failed");
     });
 }
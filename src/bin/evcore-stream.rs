@@ -0,0 +1,72 @@
+//! Standalone stream server.
+//!
+//! Serves a file-backed event stream over TCP, either as a primary that
+//! acknowledges publishes once replicated to a number of followers, or as a
+//! follower of another server.
+//!
+//! Usage:
+//!
+//! ```text
+//! evcore-stream --listen <addr> --data <path> [--replicas <n>]
+//! evcore-stream --listen <addr> --data <path> --follow <primary-addr>
+//! ```
+
+use evcore::file::FileStream;
+use evcore::replication::Server;
+
+use std::net::TcpListener;
+use std::{env, process};
+
+const USAGE: &str = "usage: evcore-stream --listen <addr> --data <path> \
+                     [--replicas <n> | --follow <primary-addr>]";
+
+fn main() {
+    let mut listen = None;
+    let mut data = None;
+    let mut replicas = 0;
+    let mut follow = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .unwrap_or_else(|| fail(&format!("missing value for {arg}")));
+        match arg.as_str() {
+            "--listen" => listen = Some(value),
+            "--data" => data = Some(value),
+            "--replicas" => {
+                replicas = value
+                    .parse()
+                    .unwrap_or_else(|_| fail(&format!("invalid replica count: {value}")))
+            }
+            "--follow" => follow = Some(value),
+            _ => fail(&format!("unknown argument: {arg}")),
+        }
+    }
+
+    let listen = listen.unwrap_or_else(|| fail("--listen is required"));
+    let data = data.unwrap_or_else(|| fail("--data is required"));
+    if follow.is_some() && replicas > 0 {
+        fail("--replicas and --follow are mutually exclusive");
+    }
+
+    let log =
+        FileStream::open(&data).unwrap_or_else(|e| fail(&format!("failed to open {data}: {e}")));
+    let listener = TcpListener::bind(&listen)
+        .unwrap_or_else(|e| fail(&format!("failed to bind {listen}: {e}")));
+
+    let server = match &follow {
+        Some(primary) => Server::follower(log, primary),
+        None => Server::primary(log, replicas),
+    };
+
+    if let Err(e) = server.serve(listener) {
+        fail(&format!("server failed: {e}"));
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("evcore-stream: {message}");
+    eprintln!("{USAGE}");
+    process::exit(2);
+}
@@ -0,0 +1,142 @@
+//! Time sources used by the sequencer.
+//!
+//! The sequencer measures time to decide when it has caught up with the
+//! stream and to pace its election loop. A [`Clock`] makes that time source
+//! explicit, so production deployments can use a monotonic clock immune to
+//! wall-clock jumps, and tests can drive time by hand with a [`ManualClock`].
+
+use std::{
+    sync::{Condvar, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A source of time.
+pub trait Clock: Sync {
+    /// Returns the time elapsed since the clock's epoch.
+    ///
+    /// Only differences between readings are meaningful; the epoch itself is
+    /// implementation-defined.
+    fn now(&self) -> Duration;
+
+    /// Blocks the calling thread until `duration` has elapsed on this clock.
+    fn sleep(&self, duration: Duration);
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration);
+    }
+}
+
+/// Wall-clock time since the Unix epoch.
+///
+/// Readings jump when the system time is adjusted. Prefer
+/// [`MonotonicClock`] unless readings must be comparable across processes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Monotonic time since the clock was created.
+///
+/// Unaffected by wall-clock adjustments. This is the clock a
+/// [`SequencerBuilder`] uses unless given another.
+///
+/// [`SequencerBuilder`]: crate::sequencer::SequencerBuilder
+#[derive(Clone, Copy, Debug)]
+pub struct MonotonicClock {
+    start: Instant,
+}
+
+impl MonotonicClock {
+    /// Creates a clock whose epoch is the current instant.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// [`Clock::sleep`] blocks until another thread advances the clock past the
+/// sleeper's deadline.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Mutex<Duration>,
+    advanced: Condvar,
+}
+
+impl ManualClock {
+    /// Creates a clock reading `start`.
+    pub fn new(start: Duration) -> Self {
+        Self {
+            now: Mutex::new(start),
+            advanced: Condvar::new(),
+        }
+    }
+
+    /// Moves the clock forward by `duration`, waking any sleepers whose
+    /// deadline has passed.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+        self.advanced.notify_all();
+    }
+
+    /// Sets the clock to `now`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `now` is earlier than the current reading.
+    pub fn set(&self, now: Duration) {
+        let mut current = self.now.lock().unwrap();
+        assert!(now >= *current, "manual clock cannot move backwards");
+        *current = now;
+        self.advanced.notify_all();
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        let deadline = *now + duration;
+        while *now < deadline {
+            now = self.advanced.wait(now).unwrap();
+        }
+    }
+}
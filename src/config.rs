@@ -0,0 +1,507 @@
+//! Sequencer options loaded from TOML files and environment variables.
+//!
+//! Enabled with the `config` feature. A [`SequencerConfig`] holds the
+//! options set by a source and leaves the rest to the
+//! [`SequencerBuilder`] it is applied to. Options are read from the
+//! `[sequencer]` table of a TOML document, other tables being left to the
+//! application:
+//!
+//! ```toml
+//! [sequencer]
+//! election_interval = "100ms"
+//! renew_interval = "100ms"
+//! activation_interval = "100ms"
+//! heartbeat_interval = "1s"
+//! catch_up = "logic_or_idle"   # or "logic", "idle"
+//! catch_up_window = "1s"
+//! lost_lease = "exit"          # or "stop"
+//! batch = 64
+//! progress_deadline = "500ms" # unset by default
+//! lease_margin = "50ms"
+//! liveness_window = "5s"      # unset by default
+//! ```
+//!
+//! or from environment variables named after the same keys, upper-cased
+//! behind a prefix, such as `EVCORE_SEQUENCER_RENEW_INTERVAL=250ms` for the
+//! prefix `EVCORE_SEQUENCER`. Durations take a unit of `ns`, `us`, `ms`,
+//! `s`, `m` or `h`.
+//!
+//! ```ignore
+//! let config = SequencerConfig::from_file("evcore.toml")?
+//!     .merge(SequencerConfig::from_env("EVCORE_SEQUENCER")?);
+//!
+//! SequencerBuilder::new()
+//!     .config(&config)
+//!     .run(&stream, &producer, &inbox, &election, logic)?;
+//! ```
+
+use crate::sequencer::{CatchUp, LostLease, SequencerBuilder};
+
+use std::{env, error, fmt, fs, io, path::Path, time::Duration};
+
+/// Keys accepted in the `[sequencer]` table.
+const KEYS: [&str; 11] = [
+    "election_interval",
+    "renew_interval",
+    "activation_interval",
+    "heartbeat_interval",
+    "catch_up",
+    "catch_up_window",
+    "lost_lease",
+    "batch",
+    "progress_deadline",
+    "lease_margin",
+    "liveness_window",
+];
+
+/// The window used when a config selects an idle catch-up strategy without
+/// giving one, and the builder has none to keep.
+const DEFAULT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Reason a configuration could not be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The document is not valid TOML.
+    Parse(String),
+    /// The `[sequencer]` table holds a key that is not an option.
+    UnknownKey(String),
+    /// An option has a value of the wrong form.
+    Value {
+        /// Key of the option.
+        key: String,
+        /// The value as given.
+        value: String,
+        /// Description of the expected form.
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "failed to read config: {e}"),
+            ConfigError::Parse(e) => write!(f, "invalid TOML: {e}"),
+            ConfigError::UnknownKey(key) => write!(f, "unknown sequencer option {key:?}"),
+            ConfigError::Value {
+                key,
+                value,
+                expected,
+            } => write!(f, "invalid value {value:?} for {key}: expected {expected}"),
+        }
+    }
+}
+
+impl error::Error for ConfigError {}
+
+/// How a config selects the catch-up strategy; the window is a separate key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Logic,
+    Idle,
+    LogicOrIdle,
+}
+
+/// Sequencer options from one or more sources, each unset unless given.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SequencerConfig {
+    election_interval: Option<Duration>,
+    renew_interval: Option<Duration>,
+    activation_interval: Option<Duration>,
+    heartbeat_interval: Option<Duration>,
+    catch_up: Option<Mode>,
+    catch_up_window: Option<Duration>,
+    lost_lease: Option<LostLease>,
+    batch: Option<usize>,
+    progress_deadline: Option<Duration>,
+    lease_margin: Option<Duration>,
+    liveness_window: Option<Duration>,
+}
+
+impl SequencerConfig {
+    /// Reads the `[sequencer]` table of a TOML document. A document without
+    /// one sets no options.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let document: toml::Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| ConfigError::Parse(e.message().to_string()))?;
+
+        let mut config = Self::default();
+        let Some(table) = document.get("sequencer") else {
+            return Ok(config);
+        };
+        let Some(table) = table.as_table() else {
+            return Err(ConfigError::Parse("`sequencer` is not a table".to_string()));
+        };
+
+        for (key, value) in table {
+            let value = match value {
+                toml::Value::String(value) => value.clone(),
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                value => format!("<{}>", value.type_str()),
+            };
+            config.set(key, &value)?;
+        }
+        Ok(config)
+    }
+
+    /// Reads the `[sequencer]` table of a TOML file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::from_toml(&fs::read_to_string(path).map_err(ConfigError::Io)?)
+    }
+
+    /// Reads options from environment variables named `{prefix}_{KEY}`,
+    /// such as `{prefix}_BATCH`. Unset variables set no options.
+    pub fn from_env(prefix: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        for key in KEYS {
+            let name = format!("{prefix}_{}", key.to_uppercase());
+            if let Ok(value) = env::var(&name) {
+                config.set(key, &value)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Combines two configs, taking each option from `overrides` where it
+    /// is set and from `self` otherwise.
+    pub fn merge(self, overrides: Self) -> Self {
+        Self {
+            election_interval: overrides.election_interval.or(self.election_interval),
+            renew_interval: overrides.renew_interval.or(self.renew_interval),
+            activation_interval: overrides.activation_interval.or(self.activation_interval),
+            heartbeat_interval: overrides.heartbeat_interval.or(self.heartbeat_interval),
+            catch_up: overrides.catch_up.or(self.catch_up),
+            catch_up_window: overrides.catch_up_window.or(self.catch_up_window),
+            lost_lease: overrides.lost_lease.or(self.lost_lease),
+            batch: overrides.batch.or(self.batch),
+            progress_deadline: overrides.progress_deadline.or(self.progress_deadline),
+            lease_margin: overrides.lease_margin.or(self.lease_margin),
+            liveness_window: overrides.liveness_window.or(self.liveness_window),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = |expected| ConfigError::Value {
+            key: key.to_string(),
+            value: value.to_string(),
+            expected,
+        };
+        let duration =
+            || parse_duration(value).ok_or_else(|| invalid("a duration such as \"100ms\""));
+
+        match key {
+            "election_interval" => self.election_interval = Some(duration()?),
+            "renew_interval" => self.renew_interval = Some(duration()?),
+            "activation_interval" => self.activation_interval = Some(duration()?),
+            "heartbeat_interval" => self.heartbeat_interval = Some(duration()?),
+            "catch_up_window" => self.catch_up_window = Some(duration()?),
+            "progress_deadline" => self.progress_deadline = Some(duration()?),
+            "lease_margin" => self.lease_margin = Some(duration()?),
+            "liveness_window" => self.liveness_window = Some(duration()?),
+            "catch_up" => {
+                self.catch_up = Some(match value {
+                    "logic" => Mode::Logic,
+                    "idle" => Mode::Idle,
+                    "logic_or_idle" => Mode::LogicOrIdle,
+                    _ => return Err(invalid("\"logic\", \"idle\" or \"logic_or_idle\"")),
+                })
+            }
+            "lost_lease" => {
+                self.lost_lease = Some(match value {
+                    "exit" => LostLease::Exit,
+                    "stop" => LostLease::Stop,
+                    _ => return Err(invalid("\"exit\" or \"stop\"")),
+                })
+            }
+            "batch" => self.batch = Some(value.parse().map_err(|_| invalid("a positive integer"))?),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+}
+
+impl<C, O> SequencerBuilder<C, O> {
+    /// Applies every option set in `config`, keeping the builder's value for
+    /// the rest.
+    ///
+    /// A catch-up window without a strategy keeps the builder's strategy if
+    /// it has a window, and selects [`CatchUp::LogicOrIdle`] otherwise.
+    pub fn config(mut self, config: &SequencerConfig) -> Self {
+        let options = &mut self.options;
+        if let Some(interval) = config.election_interval {
+            options.election_interval = interval;
+        }
+        if let Some(interval) = config.renew_interval {
+            options.renew_interval = interval;
+        }
+        if let Some(interval) = config.activation_interval {
+            options.activation_interval = interval;
+        }
+        if let Some(interval) = config.heartbeat_interval {
+            options.heartbeat_interval = interval;
+        }
+        if let Some(policy) = config.lost_lease {
+            options.lost_lease = policy;
+        }
+        if let Some(batch) = config.batch {
+            options.batch = batch;
+        }
+        if let Some(deadline) = config.progress_deadline {
+            options.progress_deadline = Some(deadline);
+        }
+        if let Some(margin) = config.lease_margin {
+            options.lease_margin = margin;
+        }
+        if let Some(window) = config.liveness_window {
+            options.liveness_window = Some(window);
+        }
+
+        let window = config.catch_up_window.unwrap_or(match options.catch_up {
+            CatchUp::Logic => DEFAULT_WINDOW,
+            CatchUp::Idle(window) | CatchUp::LogicOrIdle(window) => window,
+        });
+        let mode = config
+            .catch_up
+            .or(match (config.catch_up_window, options.catch_up) {
+                (Some(_), CatchUp::Logic) => Some(Mode::LogicOrIdle),
+                _ => None,
+            });
+        match mode {
+            Some(Mode::Logic) => options.catch_up = CatchUp::Logic,
+            Some(Mode::Idle) => options.catch_up = CatchUp::Idle(window),
+            Some(Mode::LogicOrIdle) => options.catch_up = CatchUp::LogicOrIdle(window),
+            None => {
+                if let CatchUp::Idle(current) | CatchUp::LogicOrIdle(current) =
+                    &mut options.catch_up
+                {
+                    *current = window;
+                }
+            }
+        }
+
+        self
+    }
+}
+
+/// Parses a decimal number followed by a unit, such as `250ms` or `1.5s`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let seconds = match unit.trim() {
+        "ns" => number / 1e9,
+        "us" => number / 1e6,
+        "ms" => number / 1e3,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catch_up(builder: SequencerBuilder, config: &str) -> CatchUp {
+        let config = SequencerConfig::from_toml(config).unwrap();
+        builder.config(&config).options.catch_up
+    }
+
+    #[test]
+    fn durations_take_units() {
+        for (value, expected) in [
+            ("100ns", Duration::from_nanos(100)),
+            ("250us", Duration::from_micros(250)),
+            ("250ms", Duration::from_millis(250)),
+            ("1.5s", Duration::from_millis(1500)),
+            ("2m", Duration::from_secs(120)),
+            ("1h", Duration::from_secs(3600)),
+            (" 10 ms ", Duration::from_millis(10)),
+        ] {
+            assert_eq!(parse_duration(value), Some(expected), "{value}");
+        }
+        for value in ["", "100", "ms", "10 parsecs", "-1s", "1e3ms", "1..5s"] {
+            assert_eq!(parse_duration(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn reads_sequencer_table() {
+        let config = SequencerConfig::from_toml(
+            r#"
+            [sequencer]
+            renew_interval = "250ms"
+            catch_up = "idle"
+            lost_lease = "stop"
+            batch = 64
+
+            [application]
+            name = "ignored"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            SequencerConfig {
+                renew_interval: Some(Duration::from_millis(250)),
+                catch_up: Some(Mode::Idle),
+                lost_lease: Some(LostLease::Stop),
+                batch: Some(64),
+                ..SequencerConfig::default()
+            }
+        );
+        assert_eq!(
+            SequencerConfig::from_toml("[other]\nkey = 1").unwrap(),
+            SequencerConfig::default()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for (text, key) in [
+            ("renew_interval = \"soon\"", "renew_interval"),
+            ("renew_interval = 100", "renew_interval"),
+            ("catch_up = \"eventually\"", "catch_up"),
+            ("lost_lease = \"ignore\"", "lost_lease"),
+            ("batch = -1", "batch"),
+            ("batch = \"many\"", "batch"),
+        ] {
+            match SequencerConfig::from_toml(&format!("[sequencer]\n{text}")) {
+                Err(ConfigError::Value { key: k, .. }) => assert_eq!(k, key, "{text}"),
+                other => panic!("{text}: unexpected {other:?}"),
+            }
+        }
+        assert!(matches!(
+            SequencerConfig::from_toml("sequencer = 1"),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            SequencerConfig::from_toml("[sequencer"),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_key() {
+        match SequencerConfig::from_toml("[sequencer]\nrenew_intervall = \"1s\"") {
+            Err(ConfigError::UnknownKey(key)) => assert_eq!(key, "renew_intervall"),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reads_prefixed_environment_variables() {
+        let prefix = format!("EVCORE_CONFIG_TEST_{}", std::process::id());
+        // SAFETY: the variables are unique to this test, and no other test
+        // changes the environment.
+        unsafe {
+            env::set_var(format!("{prefix}_BATCH"), "8");
+            env::set_var(format!("{prefix}_LEASE_MARGIN"), "50ms");
+        }
+
+        let config = SequencerConfig::from_env(&prefix).unwrap();
+        assert_eq!(
+            config,
+            SequencerConfig {
+                batch: Some(8),
+                lease_margin: Some(Duration::from_millis(50)),
+                ..SequencerConfig::default()
+            }
+        );
+
+        unsafe { env::set_var(format!("{prefix}_BATCH"), "eight") };
+        assert!(matches!(
+            SequencerConfig::from_env(&prefix),
+            Err(ConfigError::Value { .. })
+        ));
+    }
+
+    #[test]
+    fn merge_prefers_overrides() {
+        let base = SequencerConfig::from_toml(
+            "[sequencer]\nrenew_interval = \"1s\"\nbatch = 4\nlost_lease = \"stop\"",
+        )
+        .unwrap();
+        let overrides =
+            SequencerConfig::from_toml("[sequencer]\nbatch = 16\ncatch_up = \"logic\"").unwrap();
+
+        assert_eq!(
+            base.merge(overrides),
+            SequencerConfig {
+                renew_interval: Some(Duration::from_secs(1)),
+                batch: Some(16),
+                lost_lease: Some(LostLease::Stop),
+                catch_up: Some(Mode::Logic),
+                ..SequencerConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn catch_up_window_with_strategy() {
+        let builder = || SequencerBuilder::new().catch_up(CatchUp::Logic);
+        assert_eq!(
+            catch_up(builder(), "[sequencer]\ncatch_up = \"idle\""),
+            CatchUp::Idle(DEFAULT_WINDOW)
+        );
+        assert_eq!(
+            catch_up(
+                builder(),
+                "[sequencer]\ncatch_up = \"idle\"\ncatch_up_window = \"5s\""
+            ),
+            CatchUp::Idle(Duration::from_secs(5))
+        );
+
+        let builder = || SequencerBuilder::new().catch_up(CatchUp::Idle(Duration::from_secs(3)));
+        assert_eq!(
+            catch_up(builder(), "[sequencer]\ncatch_up = \"logic_or_idle\""),
+            CatchUp::LogicOrIdle(Duration::from_secs(3))
+        );
+        assert_eq!(
+            catch_up(
+                builder(),
+                "[sequencer]\ncatch_up = \"logic\"\ncatch_up_window = \"5s\""
+            ),
+            CatchUp::Logic
+        );
+    }
+
+    #[test]
+    fn catch_up_window_without_strategy() {
+        let window = "[sequencer]\ncatch_up_window = \"5s\"";
+        assert_eq!(
+            catch_up(
+                SequencerBuilder::new().catch_up(CatchUp::Idle(Duration::from_secs(3))),
+                window
+            ),
+            CatchUp::Idle(Duration::from_secs(5))
+        );
+        assert_eq!(
+            catch_up(
+                SequencerBuilder::new().catch_up(CatchUp::LogicOrIdle(Duration::from_secs(3))),
+                window
+            ),
+            CatchUp::LogicOrIdle(Duration::from_secs(5))
+        );
+        assert_eq!(
+            catch_up(SequencerBuilder::new().catch_up(CatchUp::Logic), window),
+            CatchUp::LogicOrIdle(Duration::from_secs(5))
+        );
+        assert_eq!(
+            catch_up(
+                SequencerBuilder::new().catch_up(CatchUp::Logic),
+                "[sequencer]"
+            ),
+            CatchUp::Logic
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Conformance checks for backend implementors.
+//!
+//! Enabled with the `conformance` feature. The functions here exercise the
+//! contracts documented on [`Stream`], [`Producer`], [`Inbox`] and
+//! [`Election`], and panic with a description of the first violation they
+//! find, so they can be called directly from a backend's own tests, as
+//! `tests/conformance_file.rs` does for the file backends:
+//!
+//! ```ignore
+//! #[test]
+//! fn file_stream_conforms() {
+//!     let path = fresh_path("stream");
+//!     conformance::stream(
+//!         || {
+//!             let stream = FileStream::open(&path).unwrap();
+//!             let producer = stream.producer();
+//!             (stream, producer)
+//!         },
+//!         0,
+//!     );
+//! }
+//! ```
+//!
+//! Receivers block until an event arrives, so a backend that loses events
+//! makes a check hang rather than fail. Run the checks under a test timeout.
+
+use crate::{
+    Receiver,
+    election::Election,
+    inbox::{Inbox, Sender},
+    stream::{Producer, Stream},
+};
+
+use std::{collections::HashSet, slice, thread, time::Duration};
+
+/// Number of events published by each stream check.
+const EVENTS: u64 = 100;
+
+/// Runs every stream check against a fresh, empty stream.
+///
+/// `open` connects to the stream under test and returns a handle together
+/// with a producer for it. Every call must connect to the same underlying
+/// stream, as a restarted process would. `base` is the offset the backend
+/// assigns to the first event of an empty stream. Offsets of later events
+/// must increase but need not be consecutive; see [`Cursor`].
+pub fn stream<S, P, F>(open: F, base: u64)
+where
+    S: Stream,
+    P: Producer,
+    F: Fn() -> (S, P),
+{
+    let (stream, producer) = open();
+    let mut cursor = Cursor::new(base);
+
+    cursor = ordered_delivery(&stream, &producer, &cursor);
+    cursor = replay_from_offset(&stream, &producer, &cursor);
+    cursor = multiple_subscribers(&stream, &producer, &cursor);
+    drop((stream, producer));
+
+    restart(&open, &cursor);
+}
+
+/// Position of the stream checks within the stream under test.
+///
+/// Backends may leave gaps between the offsets of delivered events; raft,
+/// for one, assigns an offset to the entry opening each term but never
+/// delivers it. Rather than counting events, the checks therefore find
+/// offsets by subscribing and comparing the first event delivered, relying
+/// only on each event taking up at least one offset.
+#[derive(Clone, Debug)]
+pub struct Cursor {
+    offset: u64,
+    /// Event delivered first from `offset`: the last one published before
+    /// the cursor, or `None` on an empty stream.
+    last: Option<Vec<u8>>,
+}
+
+impl Cursor {
+    /// Creates a cursor at the start of an empty stream whose first event
+    /// gets offset `base`.
+    pub fn new(base: u64) -> Self {
+        Self {
+            offset: base,
+            last: None,
+        }
+    }
+
+    /// Subscribes so that the receiver next delivers the first event
+    /// published after the cursor.
+    fn subscribe<S: Stream>(&self, stream: &S) -> S::Receiver {
+        let receiver = stream.subscribe(self.offset);
+        if let Some(last) = &self.last {
+            expect(&receiver, slice::from_ref(last), "resubscribe");
+        }
+        receiver
+    }
+
+    /// Returns an offset from which `stream` delivers `events[index]` first,
+    /// given that `events` were published right after the cursor.
+    fn locate<S: Stream>(&self, stream: &S, events: &[Vec<u8>], index: usize) -> u64 {
+        let preceding = self.last.iter();
+        let events: Vec<_> = preceding.chain(events).collect();
+        let target = index + usize::from(self.last.is_some());
+
+        // Every event takes up at least one offset, so skipping ahead by the
+        // number of events left before the target never passes it.
+        let mut offset = self.offset;
+        loop {
+            let first = stream.subscribe(offset).recv();
+            let Some(position) = events.iter().position(|&event| *event == first) else {
+                panic!(
+                    "subscription at offset {offset} delivered unexpected event {:?}",
+                    String::from_utf8_lossy(&first),
+                );
+            };
+            assert!(
+                position <= target,
+                "subscription at offset {offset} skipped {:?}",
+                String::from_utf8_lossy(events[target]),
+            );
+            if position == target {
+                return offset;
+            }
+            offset += (target - position) as u64;
+        }
+    }
+
+    /// Returns the cursor following `events`, published right after this
+    /// one.
+    fn advance<S: Stream>(&self, stream: &S, events: &[Vec<u8>]) -> Self {
+        Self {
+            offset: self.locate(stream, events, events.len() - 1),
+            last: events.last().cloned(),
+        }
+    }
+}
+
+/// Checks that a subscriber receives published events in order.
+///
+/// Starts at `cursor` and returns the cursor following the events this
+/// check published.
+pub fn ordered_delivery<S, P>(stream: &S, producer: &P, cursor: &Cursor) -> Cursor
+where
+    S: Stream,
+    P: Producer,
+{
+    let events = batch("ordered", EVENTS);
+    let receiver = cursor.subscribe(stream);
+    for event in &events {
+        producer.publish(event);
+    }
+
+    expect(&receiver, &events, "ordered delivery");
+    cursor.advance(stream, &events)
+}
+
+/// Checks that subscribing at an offset replays every event from that offset
+/// onwards, including events published before the subscription.
+///
+/// Starts at `cursor` and returns the cursor following the events this
+/// check published.
+pub fn replay_from_offset<S, P>(stream: &S, producer: &P, cursor: &Cursor) -> Cursor
+where
+    S: Stream,
+    P: Producer,
+{
+    let events = batch("replay", EVENTS);
+    for event in &events {
+        producer.publish(event);
+    }
+
+    for skip in [0, EVENTS / 2, EVENTS - 1] {
+        let offset = cursor.locate(stream, &events, skip as usize);
+        expect(
+            &stream.subscribe(offset),
+            &events[skip as usize..],
+            &format!("replay from offset {offset}"),
+        );
+    }
+    cursor.advance(stream, &events)
+}
+
+/// Checks that concurrent subscribers each receive every event, in order,
+/// while a producer publishes from another thread.
+///
+/// Starts at `cursor` and returns the cursor following the events this
+/// check published.
+pub fn multiple_subscribers<S, P>(stream: &S, producer: &P, cursor: &Cursor) -> Cursor
+where
+    S: Stream,
+    P: Producer,
+{
+    let events = batch("subscribers", EVENTS);
+    let receivers: Vec<_> = (0..3).map(|_| cursor.subscribe(stream)).collect();
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            for event in &events {
+                producer.publish(event);
+            }
+        });
+
+        for (i, event) in events.iter().enumerate() {
+            for (j, receiver) in receivers.iter().enumerate() {
+                let received = receiver.recv();
+                assert!(
+                    received == *event,
+                    "subscriber {j}: expected {:?} at position {i}, received {:?}",
+                    String::from_utf8_lossy(event),
+                    String::from_utf8_lossy(&received),
+                );
+            }
+        }
+    });
+
+    let late = cursor.subscribe(stream);
+    expect(&late, &events, "late subscriber");
+    cursor.advance(stream, &events)
+}
+
+/// Checks that published events survive reconnecting to the stream, and that
+/// a reconnected producer appends after them.
+///
+/// `open` must connect to the same underlying stream on every call. Starts
+/// at `cursor` and returns the cursor following the events this check
+/// published.
+pub fn restart<S, P, F>(open: F, cursor: &Cursor) -> Cursor
+where
+    S: Stream,
+    P: Producer,
+    F: Fn() -> (S, P),
+{
+    let before = batch("before restart", EVENTS);
+    {
+        let (_, producer) = open();
+        for event in &before {
+            producer.publish(event);
+        }
+    }
+
+    let (stream, producer) = open();
+    let after = batch("after restart", EVENTS);
+    for event in &after {
+        producer.publish(event);
+    }
+
+    let all: Vec<_> = before.iter().chain(&after).cloned().collect();
+    expect(&cursor.subscribe(&stream), &all, "restart");
+    let offset = cursor.locate(&stream, &all, before.len());
+    expect(&stream.subscribe(offset), &after, "restart from offset");
+    cursor.advance(&stream, &all)
+}
+
+/// Checks that every command sent through `sender` reaches `inbox`, and that
+/// clearing the inbox discards pending commands.
+///
+/// Inboxes do not guarantee ordering, so commands are compared as a set.
+pub fn inbox<I, T>(inbox: &I, sender: &T)
+where
+    I: Inbox,
+    T: Sender,
+{
+    let commands = batch("command", EVENTS);
+    for command in &commands {
+        sender.send(command);
+    }
+
+    let mut pending: HashSet<_> = commands.into_iter().collect();
+    while !pending.is_empty() {
+        let command = inbox.recv();
+        assert!(
+            pending.remove(&command),
+            "inbox delivered unexpected command {:?}",
+            String::from_utf8_lossy(&command),
+        );
+    }
+
+    for command in batch("cleared", EVENTS) {
+        sender.send(&command);
+    }
+    inbox.clear();
+    sender.send(b"marker");
+
+    let command = inbox.recv();
+    assert!(
+        command == b"marker",
+        "inbox delivered {:?} after being cleared",
+        String::from_utf8_lossy(&command),
+    );
+}
+
+/// Runs every election check.
+///
+/// `open` creates a contender for the same leadership; each call represents
+/// a separate sequencer. `lease` is the backend's lease duration. The
+/// checks leave no leader behind, but wait out leases between them, so the
+/// suite takes several multiples of `lease` to run.
+pub fn election<E, F>(open: F, lease: Duration)
+where
+    E: Election,
+    F: Fn() -> E,
+{
+    exclusive_leadership(&open, lease);
+    renewal_keeps_lease(&open, lease);
+    abandoned_lease(&open, lease);
+    superseded_leader(&open, lease);
+}
+
+/// Checks that only one contender can hold leadership, and that only the
+/// leader can renew it.
+pub fn exclusive_leadership<E, F>(open: F, lease: Duration)
+where
+    E: Election,
+    F: Fn() -> E,
+{
+    let (a, b) = (open(), open());
+
+    assert!(
+        a.elect(),
+        "first contender failed to acquire free leadership"
+    );
+    assert!(!b.elect(), "second contender acquired held leadership");
+    assert!(a.renew(), "leader failed to renew its lease");
+    assert!(!b.renew(), "non-leader renewed a lease it does not hold");
+    assert!(!b.elect(), "second contender acquired renewed leadership");
+
+    drop((a, b));
+    thread::sleep(lease * 2);
+}
+
+/// Checks that a leader renewing on time keeps leadership for longer than a
+/// single lease.
+pub fn renewal_keeps_lease<E, F>(open: F, lease: Duration)
+where
+    E: Election,
+    F: Fn() -> E,
+{
+    let (a, b) = (open(), open());
+
+    assert!(a.elect(), "contender failed to acquire free leadership");
+    for _ in 0..8 {
+        thread::sleep(lease / 4);
+        assert!(a.renew(), "leader failed to renew its lease in time");
+        assert!(
+            !b.elect(),
+            "contender acquired leadership from a renewing leader"
+        );
+    }
+
+    drop((a, b));
+    thread::sleep(lease * 2);
+}
+
+/// Checks that leadership abandoned by a leader becomes available to another
+/// contender once the lease runs out.
+pub fn abandoned_lease<E, F>(open: F, lease: Duration)
+where
+    E: Election,
+    F: Fn() -> E,
+{
+    let a = open();
+    assert!(a.elect(), "contender failed to acquire free leadership");
+    drop(a);
+
+    thread::sleep(lease * 2);
+    let b = open();
+    assert!(
+        b.elect(),
+        "contender failed to acquire abandoned leadership"
+    );
+
+    drop(b);
+    thread::sleep(lease * 2);
+}
+
+/// Checks that a leader which stops renewing cannot renew again once another
+/// contender has taken over.
+///
+/// Backends whose leadership outlives the lease while the leader is still
+/// connected pass trivially, since no takeover happens.
+pub fn superseded_leader<E, F>(open: F, lease: Duration)
+where
+    E: Election,
+    F: Fn() -> E,
+{
+    let (a, b) = (open(), open());
+    assert!(a.elect(), "contender failed to acquire free leadership");
+
+    thread::sleep(lease * 2);
+    if b.elect() {
+        assert!(!a.renew(), "superseded leader renewed its lease");
+        assert!(b.renew(), "new leader failed to renew its lease");
+    }
+
+    drop((a, b));
+    thread::sleep(lease * 2);
+}
+
+/// Returns `count` distinct events labelled with `label`.
+fn batch(label: &str, count: u64) -> Vec<Vec<u8>> {
+    (0..count)
+        .map(|i| format!("conformance {label} {i}").into_bytes())
+        .collect()
+}
+
+/// Receives `events.len()` events and asserts they match `events` in order.
+fn expect<R: Receiver>(receiver: &R, events: &[Vec<u8>], check: &str) {
+    for (i, event) in events.iter().enumerate() {
+        let received = receiver.recv();
+        assert!(
+            received == *event,
+            "{check}: expected {:?} at position {i}, received {:?}",
+            String::from_utf8_lossy(event),
+            String::from_utf8_lossy(&received),
+        );
+    }
+}
@@ -1,4 +1,61 @@
-use crate::{logic::Logic, Receiver, stream::Stream};
+use crate::{Receiver, logic::Logic, observer::Observer, sequencer::InvalidOption, stream::Stream};
+
+use std::{
+    fmt,
+    sync::{
+        Condvar, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A mismatch between a checksum event and the consumer's own state hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Offset of the checksum event, assuming consecutive offsets from the
+    /// one returned by [`load`](Logic::load).
+    pub offset: u64,
+    /// Hash recorded by the sequencer.
+    pub expected: u64,
+    /// Hash computed by the consumer.
+    pub actual: u64,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "state diverged at offset {}: expected hash {:#x}, computed {:#x}",
+            self.offset, self.expected, self.actual
+        )
+    }
+}
+
+/// A change in whether the leader appears alive to a consumer's watchdog.
+///
+/// Every event read from the stream counts as a sign of life, so an idle
+/// leader stays live only through the heartbeats it publishes to the stream
+/// with [`Sequencer::stream_heartbeat`]. The inbox heartbeat of
+/// [`Sequencer::heartbeat`] does not count unless processed into an event.
+/// With stream heartbeats enabled, a window a few heartbeat intervals long
+/// separates a quiet leader from a dead one; without them, a leader looks
+/// stale whenever no command arrives for the window.
+///
+/// [`Sequencer::stream_heartbeat`]: crate::sequencer::Sequencer::stream_heartbeat
+/// [`Sequencer::heartbeat`]: crate::sequencer::Sequencer::heartbeat
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Liveness {
+    /// No event has arrived for `silence`, which exceeds the window. State
+    /// built from the stream may be stale.
+    Stale {
+        /// Time since the last event, or since the loop started if none has
+        /// arrived.
+        silence: Duration,
+    },
+    /// An event arrived after the consumer was reported stale.
+    Live,
+}
 
 /// Runs the consumer loop, reading events from the given stream.
 ///
@@ -7,18 +64,315 @@ use crate::{logic::Logic, Receiver, stream::Stream};
 /// [`update`].
 ///
 /// The loop continues until [`update`] returns `false`.
+///
+/// Panics if a checksum event disagrees with the logic's state hash; see
+/// [`run_checked`] to handle divergence otherwise.
 pub fn run<S, L>(stream: &S, logic: &mut L)
 where
     S: Stream,
     L: Logic,
 {
-    let offset = logic.load();
+    run_checked(stream, logic, |divergence| panic!("{divergence}"));
+}
+
+/// Runs the consumer loop, reporting divergence to `on_divergence`.
+///
+/// Before each checksum event is passed to [`step`](Logic::step), its
+/// recorded hash is compared against [`state_hash`](Logic::state_hash).
+/// Logic that does not support hashing is never reported as diverged.
+pub fn run_checked<S, L, F>(stream: &S, logic: &mut L, on_divergence: F)
+where
+    S: Stream,
+    L: Logic,
+    F: FnMut(Divergence),
+{
+    consume(stream, logic, &(), on_divergence, None);
+}
+
+/// Runs the consumer loop, reporting each step to `observer`.
+///
+/// Panics on divergence, like [`run`].
+#[deprecated(
+    since = "0.1.5",
+    note = "use `ConsumerBuilder` with `ConsumerBuilder::observer`"
+)]
+pub fn run_observed<S, L, O>(stream: &S, logic: &mut L, observer: &O)
+where
+    S: Stream,
+    L: Logic,
+    O: Observer,
+{
+    consume(
+        stream,
+        logic,
+        observer,
+        |divergence| panic!("{divergence}"),
+        None,
+    );
+}
+
+/// Configures and runs a consumer with named options.
+///
+/// ```ignore
+/// ConsumerBuilder::new()
+///     .observer(&metrics)
+///     .on_divergence(|divergence| alert(divergence))
+///     .watchdog(Duration::from_secs(5), |liveness| {
+///         halted.store(liveness != Liveness::Live, Ordering::Relaxed)
+///     })
+///     .run(&stream, &mut logic)?;
+/// ```
+pub struct ConsumerBuilder<O = (), F = fn(Divergence), W = fn(Liveness)> {
+    observer: O,
+    on_divergence: F,
+    watchdog: Option<(Duration, W)>,
+}
+
+impl Default for ConsumerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConsumerBuilder {
+    /// Creates a builder with no observer that panics on divergence, as
+    /// [`run`] does.
+    pub fn new() -> Self {
+        Self {
+            observer: (),
+            on_divergence: |divergence| panic!("{divergence}"),
+            watchdog: None,
+        }
+    }
+}
+
+impl<O, F, W> ConsumerBuilder<O, F, W>
+where
+    O: Observer,
+    F: FnMut(Divergence),
+    W: FnMut(Liveness) + Send,
+{
+    /// Sets the observer that the loop reports each step to.
+    pub fn observer<P: Observer>(self, observer: P) -> ConsumerBuilder<P, F, W> {
+        ConsumerBuilder {
+            observer,
+            on_divergence: self.on_divergence,
+            watchdog: self.watchdog,
+        }
+    }
+
+    /// Sets the callback for checksum events that disagree with the logic's
+    /// state hash, as [`run_checked`] takes.
+    pub fn on_divergence<G: FnMut(Divergence)>(self, on_divergence: G) -> ConsumerBuilder<O, G, W> {
+        ConsumerBuilder {
+            observer: self.observer,
+            on_divergence,
+            watchdog: self.watchdog,
+        }
+    }
+
+    /// Watches for the leader going quiet, calling `on_change` with
+    /// [`Liveness::Stale`] once no event has arrived for `window`, and with
+    /// [`Liveness::Live`] when events resume. The observer is told as well.
+    ///
+    /// The leader must publish [stream heartbeats](Liveness) more often than
+    /// `window`. Setting the same window with
+    /// [`SequencerBuilder::liveness_window`] makes a sequencer that does not
+    /// refuse to start.
+    ///
+    /// [`SequencerBuilder::liveness_window`]: crate::sequencer::SequencerBuilder::liveness_window
+    ///
+    /// The loop blocks waiting for events, so `on_change` runs on a
+    /// separate thread. It cannot reach the logic directly; a flag shared
+    /// with it, or with whatever acts on its state, serves instead.
+    pub fn watchdog<V: FnMut(Liveness) + Send>(
+        self,
+        window: Duration,
+        on_change: V,
+    ) -> ConsumerBuilder<O, F, V> {
+        ConsumerBuilder {
+            observer: self.observer,
+            on_divergence: self.on_divergence,
+            watchdog: Some((window, on_change)),
+        }
+    }
+
+    /// Checks that every option has a usable value.
+    pub fn validate(&self) -> Result<(), InvalidOption> {
+        if let Some((window, _)) = &self.watchdog
+            && window.is_zero()
+        {
+            return Err(InvalidOption {
+                option: "watchdog",
+                reason: "must be greater than zero",
+            });
+        }
+        Ok(())
+    }
+
+    /// Validates the options and runs the consumer loop until [`step`]
+    /// returns `false`.
+    ///
+    /// [`step`]: Logic::step
+    pub fn run<S, L>(self, stream: &S, logic: &mut L) -> Result<(), InvalidOption>
+    where
+        S: Stream,
+        L: Logic,
+    {
+        self.validate()?;
+        let Some((window, on_change)) = self.watchdog else {
+            consume(stream, logic, &self.observer, self.on_divergence, None);
+            return Ok(());
+        };
+
+        let watchdog = Watchdog::new();
+        thread::scope(|s| {
+            s.spawn(|| watchdog.watch(window, &self.observer, on_change));
+            consume(
+                stream,
+                logic,
+                &self.observer,
+                self.on_divergence,
+                Some(&watchdog),
+            );
+            watchdog.stop();
+        });
+        Ok(())
+    }
+}
+
+/// Time of the last event, shared between the consumer loop and the thread
+/// watching it.
+struct Watchdog {
+    start: Instant,
+    /// Nanoseconds from `start` to the last event.
+    last: AtomicU64,
+    stale: AtomicBool,
+    stopped: AtomicBool,
+    lock: Mutex<()>,
+    wake: Condvar,
+}
+
+impl Watchdog {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            last: AtomicU64::new(0),
+            stale: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            wake: Condvar::new(),
+        }
+    }
+
+    /// Records an event, waking the watcher if it reported the leader stale.
+    fn event(&self) {
+        let now = self.start.elapsed().as_nanos() as u64;
+        self.last.store(now, Ordering::SeqCst);
+        if self.stale.load(Ordering::SeqCst) {
+            let _guard = self.lock.lock().unwrap();
+            self.wake.notify_one();
+        }
+    }
+
+    fn stop(&self) {
+        let _guard = self.lock.lock().unwrap();
+        self.stopped.store(true, Ordering::Relaxed);
+        self.wake.notify_one();
+    }
+
+    /// Reports changes in liveness until stopped. Sleeps until the window
+    /// would lapse while events arrive, and until woken while stale.
+    fn watch<O, W>(&self, window: Duration, observer: &O, mut on_change: W)
+    where
+        O: Observer,
+        W: FnMut(Liveness),
+    {
+        let mut report = |liveness| {
+            observer.liveness(liveness);
+            on_change(liveness);
+        };
+
+        let mut guard = self.lock.lock().unwrap();
+        // Time of the last event before the leader was reported stale.
+        let mut stale_at = None;
+        while !self.stopped.load(Ordering::Relaxed) {
+            let last = Duration::from_nanos(self.last.load(Ordering::SeqCst));
+            let silence = self.start.elapsed().saturating_sub(last);
+
+            match stale_at {
+                Some(at) if last > at => {
+                    stale_at = None;
+                    self.stale.store(false, Ordering::SeqCst);
+                    drop(guard);
+                    report(Liveness::Live);
+                    guard = self.lock.lock().unwrap();
+                }
+                Some(_) => guard = self.wake.wait(guard).unwrap(),
+                None if silence >= window => {
+                    stale_at = Some(last);
+                    self.stale.store(true, Ordering::SeqCst);
+                    drop(guard);
+                    report(Liveness::Stale { silence });
+                    guard = self.lock.lock().unwrap();
+                }
+                None => {
+                    guard = self.wake.wait_timeout(guard, window - silence).unwrap().0;
+                }
+            }
+        }
+    }
+}
+
+fn consume<S, L, O, F>(
+    stream: &S,
+    logic: &mut L,
+    observer: &O,
+    mut on_divergence: F,
+    watchdog: Option<&Watchdog>,
+) where
+    S: Stream,
+    L: Logic,
+    O: Observer,
+    F: FnMut(Divergence),
+{
+    let mut offset = logic.load();
+    let mut caught_up = false;
     let receiver = stream.subscribe(offset);
 
     loop {
         let event = receiver.recv();
-        if !logic.step(&event) {
+        if let Some(watchdog) = watchdog {
+            watchdog.event();
+        }
+
+        if let Some(expected) = logic.read_checksum(&event)
+            && let Some(actual) = logic.state_hash()
+            && actual != expected
+        {
+            on_divergence(Divergence {
+                offset,
+                expected,
+                actual,
+            });
+        }
+
+        if let Some(timestamp) = logic.read_heartbeat(&event) {
+            observer.heartbeat(timestamp);
+        }
+
+        let start = Instant::now();
+        let cont = logic.step(&event);
+        observer.step(&event, start.elapsed());
+
+        if !caught_up && logic.caught_up() {
+            caught_up = true;
+            observer.caught_up();
+        }
+
+        if !cont {
             break;
         }
+        offset += 1;
     }
-}
\ No newline at end of file
+}
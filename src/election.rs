@@ -1,3 +1,14 @@
+use std::{thread, time::Duration};
+
+/// The current holder of leadership, as recorded by the election backend.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Leader {
+    /// Identity of the holder, unique among candidates.
+    pub id: String,
+    /// Address at which the holder accepts commands, if it advertised one.
+    pub address: Option<String>,
+}
+
 /// Leader election for sequencer redundancy.
 ///
 /// In an event-driven architecture, multiple sequencers may exist for redundancy,
@@ -21,4 +32,72 @@ pub trait Election: Sync {
     ///
     /// Returns `true` if the lease was successfully renewed.
     fn renew(&self) -> bool;
+
+    /// Returns how long leadership lasts after a successful [`elect`] or
+    /// [`renew`], or `None` if it is not held for a fixed term.
+    ///
+    /// The sequencer counts the lease from the start of the call that won or
+    /// renewed it, and stops publishing once it may have expired even if no
+    /// renewal has failed yet.
+    ///
+    /// [`elect`]: Election::elect
+    /// [`renew`]: Election::renew
+    fn lease(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Gives up leadership if this candidate holds it, so that a standby
+    /// can take over without waiting for the lease to expire.
+    ///
+    /// The default does nothing, leaving the lease to expire.
+    fn resign(&self) {}
+
+    /// Returns the current leader, or `None` if no candidate holds an
+    /// unexpired lease or the backend cannot tell.
+    fn leader(&self) -> Option<Leader> {
+        None
+    }
+}
+
+/// Reports changes of leadership by polling [`Election::leader`].
+///
+/// Each call to [`next`](Iterator::next) blocks until the leader differs
+/// from the one last returned, the first call returning the current leader
+/// straight away. The iterator never ends.
+///
+/// ```ignore
+/// for leader in Watch::new(&election, Duration::from_millis(500)) {
+///     route_commands_to(leader.and_then(|leader| leader.address));
+/// }
+/// ```
+pub struct Watch<'a, E: ?Sized> {
+    election: &'a E,
+    interval: Duration,
+    last: Option<Option<Leader>>,
+}
+
+impl<'a, E: Election + ?Sized> Watch<'a, E> {
+    /// Watches `election`, checking for a new leader every `interval`.
+    pub fn new(election: &'a E, interval: Duration) -> Self {
+        Self {
+            election,
+            interval,
+            last: None,
+        }
+    }
+}
+
+impl<E: Election + ?Sized> Iterator for Watch<'_, E> {
+    type Item = Option<Leader>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let leader = self.election.leader();
+            if self.last.as_ref() != Some(&leader) {
+                self.last = Some(leader.clone());
+                return Some(leader);
+            }
+            thread::sleep(self.interval);
+        }
+    }
 }
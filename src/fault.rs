@@ -0,0 +1,480 @@
+//! Fault-injecting wrappers around streams, producers, inboxes and elections.
+//!
+//! Enabled with the `fault` feature. An [`Injector`] wraps existing backends
+//! so that consumers and sequencers can be exercised under adversity:
+//! duplicated and delayed deliveries, dropped inbox commands, stalled
+//! publishes and failed lease renewals. The wrappers compose with any
+//! backend, real or in-memory.
+//!
+//! Faults are drawn from a single seeded generator shared by every wrapper
+//! created from the same injector. Delays are measured on the injector's
+//! [`Clock`], so the wrappers can also run inside a simulation by passing
+//! its clock to [`Injector::with_clock`].
+//!
+//! ```ignore
+//! let faults = Faults {
+//!     duplicate: 0.01,
+//!     renew_failure: 0.001,
+//!     ..Faults::default()
+//! };
+//! let injector = Injector::new(42, faults);
+//!
+//! SequencerBuilder::new().run(
+//!     &injector.stream(stream),
+//!     &injector.producer(producer),
+//!     &injector.inbox(inbox),
+//!     &injector.election(election),
+//!     logic,
+//! )?;
+//! ```
+
+use crate::{
+    Receiver,
+    clock::{Clock, MonotonicClock},
+    election::{Election, Leader},
+    inbox::{Inbox, Sender},
+    rng::Rng,
+    stream::{Producer, Stream},
+};
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Probabilities and bounds of injected faults.
+///
+/// All probabilities are in `[0, 1]` and default to zero, so a default
+/// configuration passes every call through unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    /// Probability that an event received from a stream is delivered twice.
+    pub duplicate: f64,
+    /// Probability that a receive from a stream or inbox is delayed.
+    pub delay: f64,
+    /// Maximum duration of a receive delay.
+    pub max_delay: Duration,
+    /// Probability that a command received from an inbox is dropped.
+    pub drop: f64,
+    /// Probability that a publish stalls before reaching the producer.
+    pub publish_stall: f64,
+    /// Maximum duration of a publish stall.
+    pub max_publish_stall: Duration,
+    /// Probability that a lease renewal reports failure without reaching the
+    /// election backend.
+    pub renew_failure: f64,
+}
+
+struct Shared {
+    faults: Faults,
+    rng: Mutex<Rng>,
+    clock: Box<dyn Clock + Send>,
+}
+
+impl Shared {
+    fn chance(&self, probability: f64) -> bool {
+        self.rng.lock().unwrap().chance(probability)
+    }
+
+    /// Sleeps for a random duration up to `max` with the given probability.
+    fn stall(&self, probability: f64, max: Duration) {
+        let stall = {
+            let mut rng = self.rng.lock().unwrap();
+            rng.chance(probability).then(|| rng.jitter(max))
+        };
+        if let Some(duration) = stall {
+            self.clock.sleep(duration);
+        }
+    }
+}
+
+/// Source of faults for the wrappers it creates.
+#[derive(Clone)]
+pub struct Injector {
+    shared: Arc<Shared>,
+}
+
+impl Injector {
+    /// Creates an injector drawing faults from `seed`, timing delays with a
+    /// [`MonotonicClock`].
+    pub fn new(seed: u64, faults: Faults) -> Self {
+        Self::with_clock(seed, faults, MonotonicClock::new())
+    }
+
+    /// Creates an injector timing delays with `clock`.
+    pub fn with_clock<C>(seed: u64, faults: Faults, clock: C) -> Self
+    where
+        C: Clock + Send + 'static,
+    {
+        Self {
+            shared: Arc::new(Shared {
+                faults,
+                rng: Mutex::new(Rng::new(seed)),
+                clock: Box::new(clock),
+            }),
+        }
+    }
+
+    /// Wraps a stream, duplicating and delaying the events its receivers
+    /// deliver.
+    pub fn stream<S: Stream>(&self, inner: S) -> FaultyStream<S> {
+        FaultyStream {
+            inner,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Wraps a producer, stalling publishes.
+    pub fn producer<P: Producer>(&self, inner: P) -> FaultyProducer<P> {
+        FaultyProducer {
+            inner,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Wraps an inbox, delaying and dropping received commands.
+    pub fn inbox<I: Inbox>(&self, inner: I) -> FaultyInbox<I> {
+        FaultyInbox {
+            inner,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Wraps an election, failing lease renewals.
+    pub fn election<E: Election>(&self, inner: E) -> FaultyElection<E> {
+        FaultyElection {
+            inner,
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// A [`Stream`] whose receivers duplicate and delay events.
+pub struct FaultyStream<S> {
+    inner: S,
+    shared: Arc<Shared>,
+}
+
+impl<S: Stream> Stream for FaultyStream<S> {
+    type Receiver = FaultyReceiver<S::Receiver>;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        FaultyReceiver {
+            inner: self.inner.subscribe(offset),
+            shared: Arc::clone(&self.shared),
+            repeat: Mutex::new(None),
+        }
+    }
+}
+
+/// Receiver of a [`FaultyStream`].
+pub struct FaultyReceiver<R> {
+    inner: R,
+    shared: Arc<Shared>,
+    repeat: Mutex<Option<Vec<u8>>>,
+}
+
+impl<R: Receiver> Receiver for FaultyReceiver<R> {
+    fn recv(&self) -> Vec<u8> {
+        let faults = &self.shared.faults;
+        self.shared.stall(faults.delay, faults.max_delay);
+
+        let mut repeat = self.repeat.lock().unwrap();
+        if let Some(event) = repeat.take() {
+            return event;
+        }
+
+        let event = self.inner.recv();
+        if self.shared.chance(faults.duplicate) {
+            *repeat = Some(event.clone());
+        }
+        event
+    }
+}
+
+/// A [`Producer`] whose publishes may stall.
+///
+/// A batch is forwarded to the inner producer's
+/// [`publish_batch`](Producer::publish_batch) whole, after the same stall
+/// decision as a single publish.
+pub struct FaultyProducer<P> {
+    inner: P,
+    shared: Arc<Shared>,
+}
+
+impl<P: Producer> Producer for FaultyProducer<P> {
+    fn publish(&self, data: &[u8]) {
+        let faults = &self.shared.faults;
+        self.shared
+            .stall(faults.publish_stall, faults.max_publish_stall);
+
+        self.inner.publish(data);
+    }
+
+    fn publish_batch(&self, events: &[Vec<u8>]) {
+        let faults = &self.shared.faults;
+        self.shared
+            .stall(faults.publish_stall, faults.max_publish_stall);
+
+        self.inner.publish_batch(events);
+    }
+}
+
+/// An [`Inbox`] that delays and drops received commands.
+///
+/// Commands are dropped on receipt, after leaving the underlying inbox, as
+/// if lost between the inbox and the sequencer.
+pub struct FaultyInbox<I> {
+    inner: I,
+    shared: Arc<Shared>,
+}
+
+impl<I: Inbox> Receiver for FaultyInbox<I> {
+    fn recv(&self) -> Vec<u8> {
+        let faults = &self.shared.faults;
+        loop {
+            self.shared.stall(faults.delay, faults.max_delay);
+
+            let command = self.inner.recv();
+            if !self.shared.chance(faults.drop) {
+                return command;
+            }
+        }
+    }
+}
+
+impl<I: Inbox> Sender for FaultyInbox<I> {
+    fn send(&self, command: &[u8]) {
+        self.inner.send(command);
+    }
+}
+
+impl<I: Inbox> Inbox for FaultyInbox<I> {
+    fn clear(&self) {
+        self.inner.clear();
+    }
+
+    fn depth(&self) -> Option<usize> {
+        self.inner.depth()
+    }
+}
+
+/// An [`Election`] whose lease renewals may fail.
+pub struct FaultyElection<E> {
+    inner: E,
+    shared: Arc<Shared>,
+}
+
+impl<E: Election> Election for FaultyElection<E> {
+    fn elect(&self) -> bool {
+        self.inner.elect()
+    }
+
+    fn renew(&self) -> bool {
+        !self.shared.chance(self.shared.faults.renew_failure) && self.inner.renew()
+    }
+
+    fn lease(&self) -> Option<Duration> {
+        self.inner.lease()
+    }
+
+    fn resign(&self) {
+        self.inner.resign();
+    }
+
+    fn leader(&self) -> Option<Leader> {
+        self.inner.leader()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        collections::VecDeque,
+        panic::{self, AssertUnwindSafe},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// A finite stream and inbox that panics once exhausted.
+    struct Queue(Mutex<VecDeque<Vec<u8>>>);
+
+    impl Queue {
+        fn new(items: &[&[u8]]) -> Self {
+            Self(Mutex::new(items.iter().map(|item| item.to_vec()).collect()))
+        }
+    }
+
+    impl Receiver for &Queue {
+        fn recv(&self) -> Vec<u8> {
+            let item = self.0.lock().unwrap().pop_front();
+            item.expect("queue exhausted")
+        }
+    }
+
+    impl Stream for &Queue {
+        type Receiver = Self;
+
+        fn subscribe(&self, _offset: u64) -> Self {
+            self
+        }
+    }
+
+    impl Sender for &Queue {
+        fn send(&self, command: &[u8]) {
+            self.0.lock().unwrap().push_back(command.to_vec());
+        }
+    }
+
+    impl Inbox for &Queue {
+        fn clear(&self) {
+            self.0.lock().unwrap().clear();
+        }
+
+        fn depth(&self) -> Option<usize> {
+            Some(self.0.lock().unwrap().len())
+        }
+    }
+
+    /// A clock that records sleeps instead of blocking.
+    #[derive(Clone, Default)]
+    struct Sleeps(Arc<Mutex<Vec<Duration>>>);
+
+    impl Clock for Sleeps {
+        fn now(&self) -> Duration {
+            Duration::ZERO
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.0.lock().unwrap().push(duration);
+        }
+    }
+
+    /// An election that always renews, counting renewals.
+    #[derive(Default)]
+    struct Renewals(AtomicUsize);
+
+    impl Election for &Renewals {
+        fn elect(&self) -> bool {
+            true
+        }
+
+        fn renew(&self) -> bool {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+    }
+
+    #[test]
+    fn duplicate_delivers_every_event_twice() {
+        let queue = Queue::new(&[b"first", b"second"]);
+        let faults = Faults {
+            duplicate: 1.0,
+            ..Faults::default()
+        };
+        let receiver = Injector::new(1, faults).stream(&queue).subscribe(0);
+
+        let events: Vec<_> = (0..4).map(|_| receiver.recv()).collect();
+        assert_eq!(events, [&b"first"[..], b"first", b"second", b"second"]);
+    }
+
+    #[test]
+    fn drop_discards_every_command() {
+        let queue = Queue::new(&[b"first", b"second"]);
+        let faults = Faults {
+            drop: 1.0,
+            ..Faults::default()
+        };
+        let inbox = Injector::new(1, faults).inbox(&queue);
+
+        // Receiving drains the inbox without returning, until it panics on
+        // the exhausted queue.
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| inbox.recv())).is_err());
+        assert_eq!(inbox.depth(), Some(0));
+    }
+
+    #[test]
+    fn delay_sleeps_before_each_receive() {
+        let max_delay = Duration::from_millis(10);
+        let faults = Faults {
+            delay: 1.0,
+            max_delay,
+            ..Faults::default()
+        };
+        let sleeps = Sleeps::default();
+        let injector = Injector::with_clock(1, faults, sleeps.clone());
+        let stream = Queue::new(&[b"event"]);
+        let inbox = Queue::new(&[b"command"]);
+
+        assert_eq!(injector.stream(&stream).subscribe(0).recv(), b"event");
+        assert_eq!(injector.inbox(&inbox).recv(), b"command");
+
+        let sleeps = sleeps.0.lock().unwrap();
+        assert_eq!(sleeps.len(), 2);
+        assert!(sleeps.iter().all(|&sleep| sleep < max_delay));
+    }
+
+    #[test]
+    fn no_faults_pass_calls_through() {
+        let sleeps = Sleeps::default();
+        let injector = Injector::with_clock(1, Faults::default(), sleeps.clone());
+        let stream = Queue::new(&[b"first", b"second"]);
+        let inbox = Queue::new(&[b"command"]);
+        let renewals = Renewals::default();
+
+        let receiver = injector.stream(&stream).subscribe(0);
+        assert_eq!(receiver.recv(), b"first");
+        assert_eq!(receiver.recv(), b"second");
+        assert_eq!(injector.inbox(&inbox).recv(), b"command");
+        assert!(injector.election(&renewals).renew());
+
+        assert_eq!(renewals.0.load(Ordering::Relaxed), 1);
+        assert!(sleeps.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn renew_failure_skips_inner_election() {
+        let renewals = Renewals::default();
+        let faults = Faults {
+            renew_failure: 1.0,
+            ..Faults::default()
+        };
+        let election = Injector::new(1, faults).election(&renewals);
+
+        assert!(!election.renew());
+        assert!(!election.renew());
+        assert_eq!(renewals.0.load(Ordering::Relaxed), 0);
+    }
+
+    /// Records the size of every call it receives.
+    #[derive(Default)]
+    struct Recorder {
+        calls: Mutex<Vec<usize>>,
+    }
+
+    impl Producer for &Recorder {
+        fn publish(&self, _data: &[u8]) {
+            self.calls.lock().unwrap().push(1);
+        }
+
+        fn publish_batch(&self, events: &[Vec<u8>]) {
+            self.calls.lock().unwrap().push(events.len());
+        }
+    }
+
+    #[test]
+    fn batch_reaches_inner_producer_whole() {
+        let recorder = Recorder::default();
+        let faults = Faults {
+            publish_stall: 1.0,
+            max_publish_stall: Duration::from_millis(1),
+            ..Faults::default()
+        };
+        let producer = Injector::new(1, faults).producer(&recorder);
+
+        producer.publish(b"single");
+        producer.publish_batch(&[b"first".to_vec(), b"second".to_vec()]);
+        assert_eq!(*recorder.calls.lock().unwrap(), [1, 2]);
+    }
+}
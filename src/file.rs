@@ -0,0 +1,554 @@
+//! File-backed implementations for single-host deployments.
+//!
+//! These backends rely only on the local filesystem and are intended for
+//! running hot-standby processes on the same machine, or as the storage
+//! beneath a network-facing stream server.
+
+use crate::{
+    Receiver,
+    election::{Election, Leader},
+    stream::{Producer, Stream},
+};
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Largest event a [`FileStream`] holds. Longer length prefixes are taken
+/// as corruption, so that reading one never allocates more than this.
+const MAX_RECORD: usize = 64 << 20;
+
+/// Lease-based [`Election`] over a shared lock file.
+///
+/// The file holds a single lease record: the current holder's identity, the
+/// wall-clock time at which its lease expires, and the address it
+/// [advertises](FileElection::advertise), if any. Every read-modify-write of
+/// the record is performed under an exclusive `flock`, so concurrent
+/// candidates on the same host observe a consistent view.
+///
+/// A candidate wins [`elect`] when the record is empty, expired, or already
+/// its own. The leader extends its lease with [`renew`], which fails once the
+/// lease has lapsed or another process holds it. If the leader dies or stops
+/// renewing, a standby takes over within `lease` plus its election interval;
+/// one that [resigns](Election::resign) clears the record, letting a standby
+/// take over at its next election.
+///
+/// [`elect`]: Election::elect
+/// [`renew`]: Election::renew
+pub struct FileElection {
+    file: File,
+    id: String,
+    address: Option<String>,
+    lease: Duration,
+}
+
+/// A lease record: holder, expiry in Unix nanoseconds, and address.
+type Record<'a> = (&'a str, u64, Option<&'a str>);
+
+impl FileElection {
+    /// Opens (or creates) the lock file at `path`.
+    ///
+    /// Each instance generates a unique holder identity from the process ID
+    /// and creation time, so multiple candidates may share a process.
+    pub fn new<P: AsRef<Path>>(path: P, lease: Duration) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        Ok(Self {
+            file,
+            id: format!("{}-{}", process::id(), now()),
+            address: None,
+            lease,
+        })
+    }
+
+    /// Sets the address at which this candidate accepts commands, recorded
+    /// with its lease and reported by [`leader`](Election::leader).
+    pub fn advertise(mut self, address: impl Into<String>) -> Self {
+        self.address = Some(address.into());
+        self
+    }
+
+    /// Returns the identity written into the lease record by this candidate.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Runs `f` against the lease record while holding the file lock.
+    fn locked<T>(&self, f: impl FnOnce(&File, Option<Record>) -> io::Result<T>) -> io::Result<T> {
+        self.file.lock()?;
+        let result = self.read_locked(f);
+        self.file.unlock()?;
+        result
+    }
+
+    fn read_locked<T>(
+        &self,
+        f: impl FnOnce(&File, Option<Record>) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut file = &self.file;
+        let mut contents = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut contents)?;
+        f(file, parse_record(&contents))
+    }
+
+    /// Runs `f` against the current lease record and the time while holding
+    /// the file lock.
+    ///
+    /// If `f` returns `true`, the record is rewritten with this candidate as
+    /// holder and a fresh expiry.
+    fn update(&self, f: impl FnOnce(Option<(&str, u64)>, u64) -> bool) -> io::Result<bool> {
+        self.locked(|mut file, record| {
+            let now = now();
+            if !f(record.map(|(holder, expiry, _)| (holder, expiry)), now) {
+                return Ok(false);
+            }
+
+            let expiry = now + self.lease.as_nanos() as u64;
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            match &self.address {
+                Some(address) => writeln!(file, "{} {} {}", self.id, expiry, address)?,
+                None => writeln!(file, "{} {}", self.id, expiry)?,
+            }
+            file.sync_data()?;
+
+            Ok(true)
+        })
+    }
+}
+
+/// Parses a record written by [`FileElection::update`].
+fn parse_record(contents: &str) -> Option<Record<'_>> {
+    let mut parts = contents.trim_end().splitn(3, ' ');
+    let holder = parts.next()?;
+    let expiry = parts.next()?.parse().ok()?;
+    Some((holder, expiry, parts.next()))
+}
+
+impl Election for FileElection {
+    fn elect(&self) -> bool {
+        self.update(|record, now| match record {
+            Some((holder, expiry)) => holder == self.id || expiry <= now,
+            None => true,
+        })
+        .unwrap_or(false)
+    }
+
+    fn renew(&self) -> bool {
+        self.update(|record, now| {
+            matches!(record, Some((holder, expiry)) if holder == self.id && now < expiry)
+        })
+        .unwrap_or(false)
+    }
+
+    fn lease(&self) -> Option<Duration> {
+        Some(self.lease)
+    }
+
+    fn resign(&self) {
+        let _ = self.locked(|file, record| match record {
+            Some((holder, _, _)) if holder == self.id => {
+                file.set_len(0)?;
+                file.sync_data()
+            }
+            _ => Ok(()),
+        });
+    }
+
+    fn leader(&self) -> Option<Leader> {
+        self.locked(|_, record| {
+            Ok(record
+                .filter(|&(_, expiry, _)| now() < expiry)
+                .map(|(holder, _, address)| Leader {
+                    id: holder.to_string(),
+                    address: address.map(str::to_string),
+                }))
+        })
+        .ok()
+        .flatten()
+    }
+}
+
+/// [`Stream`] backed by an append-only file.
+///
+/// Each event is stored as a little-endian `u32` length followed by its
+/// payload, and offsets are record indices. A torn record at the tail, left by
+/// a crash mid-write, is discarded on open.
+///
+/// The file must be written by a single process. Receivers created by
+/// [`subscribe`](Stream::subscribe) are woken by appends made through the same
+/// [`FileStream`]; to share the stream across processes or hosts, serve it
+/// with [`replication::Server`](crate::replication::Server).
+#[derive(Clone)]
+pub struct FileStream {
+    log: Arc<FileLog>,
+}
+
+struct FileLog {
+    path: PathBuf,
+    state: Mutex<LogState>,
+    appended: Condvar,
+}
+
+struct LogState {
+    file: File,
+    /// File position of each record.
+    positions: Vec<u64>,
+    end: u64,
+}
+
+impl FileStream {
+    /// Opens (or creates) the log file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let mut positions = Vec::new();
+        let mut end = 0;
+        let mut reader = BufReader::new(&mut file);
+        let mut buf = Vec::new();
+        loop {
+            match read_record(&mut reader, &mut buf) {
+                Ok(len) => {
+                    positions.push(end);
+                    end += 4 + len as u64;
+                }
+                // A torn tail from a crash mid-append is cut off below; any
+                // other error must not truncate the durable events after it.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        file.set_len(end)?;
+        file.seek(SeekFrom::Start(end))?;
+
+        Ok(Self {
+            log: Arc::new(FileLog {
+                path,
+                state: Mutex::new(LogState {
+                    file,
+                    positions,
+                    end,
+                }),
+                appended: Condvar::new(),
+            }),
+        })
+    }
+
+    /// Creates a producer that appends to this stream.
+    pub fn producer(&self) -> FileProducer {
+        FileProducer {
+            stream: self.clone(),
+        }
+    }
+
+    /// Returns the number of events in the stream.
+    pub fn len(&self) -> u64 {
+        self.log.state.lock().unwrap().positions.len() as u64
+    }
+
+    /// Returns `true` if the stream holds no events.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends an event, syncs it to disk, and returns its offset.
+    pub fn append(&self, data: &[u8]) -> io::Result<u64> {
+        self.append_batch(&[data])
+    }
+
+    /// Appends several events with a single sync to disk, and returns the
+    /// offset of the first.
+    ///
+    /// Fails without appending anything if an event is longer than 64 MiB.
+    pub fn append_batch<T: AsRef<[u8]>>(&self, events: &[T]) -> io::Result<u64> {
+        if events.iter().any(|data| data.as_ref().len() > MAX_RECORD) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "event exceeds the file stream's maximum size",
+            ));
+        }
+        let mut state = self.log.state.lock().unwrap();
+
+        let mut records = Vec::new();
+        let mut lengths = Vec::with_capacity(events.len());
+        for data in events {
+            let data = data.as_ref();
+            records.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            records.extend_from_slice(data);
+            lengths.push(4 + data.len() as u64);
+        }
+        state.file.write_all(&records)?;
+        state.file.sync_data()?;
+
+        let offset = state.positions.len() as u64;
+        for length in lengths {
+            let end = state.end;
+            state.positions.push(end);
+            state.end += length;
+        }
+        self.log.appended.notify_all();
+
+        Ok(offset)
+    }
+
+    /// Waits up to `timeout` for the event at `offset` to be appended, and
+    /// returns whether it has been.
+    #[cfg(feature = "replication")]
+    pub(crate) fn wait_for(&self, offset: u64, timeout: Duration) -> bool {
+        let state = self.log.state.lock().unwrap();
+        let (state, _) = self
+            .log
+            .appended
+            .wait_timeout_while(state, timeout, |state| {
+                state.positions.len() as u64 <= offset
+            })
+            .unwrap();
+
+        state.positions.len() as u64 > offset
+    }
+
+    fn position(&self, offset: u64) -> u64 {
+        let state = self.log.state.lock().unwrap();
+        let state = self
+            .log
+            .appended
+            .wait_while(state, |state| state.positions.len() as u64 <= offset)
+            .unwrap();
+
+        state.positions[offset as usize]
+    }
+}
+
+impl Stream for FileStream {
+    type Receiver = FileReceiver;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        FileReceiver {
+            stream: self.clone(),
+            cursor: Mutex::new((None, offset)),
+        }
+    }
+}
+
+/// Receiver reading a [`FileStream`] sequentially through its own file handle.
+pub struct FileReceiver {
+    stream: FileStream,
+    cursor: Mutex<(Option<BufReader<File>>, u64)>,
+}
+
+impl Receiver for FileReceiver {
+    fn recv(&self) -> Vec<u8> {
+        let mut cursor = self.cursor.lock().unwrap();
+        let (reader, next) = &mut *cursor;
+        let position = self.stream.position(*next);
+
+        let reader = match reader {
+            Some(reader) => reader,
+            None => {
+                let mut file =
+                    File::open(&self.stream.log.path).expect("failed to open file stream");
+                file.seek(SeekFrom::Start(position))
+                    .expect("failed to seek file stream");
+                reader.insert(BufReader::new(file))
+            }
+        };
+
+        let mut data = Vec::new();
+        read_record(reader, &mut data).expect("failed to read from file stream");
+        *next += 1;
+
+        data
+    }
+}
+
+/// [`Producer`] appending to a [`FileStream`].
+///
+/// Each publish, or batch of them, is synced to disk before returning. If the write fails, the
+/// producer panics, since durability can no longer be guaranteed.
+pub struct FileProducer {
+    stream: FileStream,
+}
+
+impl Producer for FileProducer {
+    fn publish(&self, data: &[u8]) {
+        self.stream
+            .append(data)
+            .expect("failed to append to file stream");
+    }
+
+    fn publish_batch(&self, events: &[Vec<u8>]) {
+        self.stream
+            .append_batch(events)
+            .expect("failed to append to file stream");
+    }
+}
+
+/// Reads every complete event of a [`FileStream`] file, in offset order.
+///
+/// Unlike [`FileStream::open`], the file is neither created nor repaired, so
+/// this is safe to use on recordings and exports from offline tools. A torn
+/// record at the tail is ignored.
+pub fn read_events<P: AsRef<Path>>(path: P) -> io::Result<Vec<Vec<u8>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut events = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match read_record(&mut reader, &mut buf) {
+            Ok(_) => events.push(buf.clone()),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(events),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Reads one length-prefixed record into `data`, returning its length.
+fn read_record(reader: &mut impl Read, data: &mut Vec<u8>) -> io::Result<usize> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_RECORD {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "record length exceeds the maximum event size",
+        ));
+    }
+
+    data.resize(len, 0);
+    reader.read_exact(data)?;
+
+    Ok(len)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    fn lock_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "evcore-file-election-{}-{name}-{}",
+            process::id(),
+            now()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn open_discards_torn_tail() {
+        let path = lock_file("torn-stream");
+        let stream = FileStream::open(&path).unwrap();
+        stream.append(b"first").unwrap();
+        stream.append(b"second").unwrap();
+        drop(stream);
+
+        let complete = std::fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&10u32.to_le_bytes()).unwrap();
+        file.write_all(b"thi").unwrap();
+        drop(file);
+
+        let stream = FileStream::open(&path).unwrap();
+        assert_eq!(stream.len(), 2);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), complete);
+        stream.append(b"third").unwrap();
+        assert_eq!(
+            read_events(&path).unwrap(),
+            [&b"first"[..], b"second", b"third"]
+        );
+    }
+
+    #[test]
+    fn open_rejects_corrupt_length() {
+        let path = lock_file("corrupt-stream");
+        let stream = FileStream::open(&path).unwrap();
+        stream.append(b"first").unwrap();
+        drop(stream);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        file.write_all(b"durable events may follow").unwrap();
+        drop(file);
+        let size = std::fs::metadata(&path).unwrap().len();
+
+        let error = FileStream::open(&path).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+    }
+
+    #[test]
+    fn oversized_event_is_rejected() {
+        let stream = FileStream::open(lock_file("oversized-stream")).unwrap();
+        let error = stream.append(&vec![0; MAX_RECORD + 1]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(stream.is_empty());
+    }
+
+    #[test]
+    fn election_is_exclusive() {
+        let path = lock_file("exclusive");
+        let a = FileElection::new(&path, Duration::from_secs(10)).unwrap();
+        let b = FileElection::new(&path, Duration::from_secs(10)).unwrap();
+
+        assert!(a.elect());
+        assert!(!b.elect());
+        assert!(a.elect(), "the holder may elect itself again");
+        assert!(a.renew());
+        assert!(!b.renew());
+    }
+
+    #[test]
+    fn expired_lease_passes_to_standby() {
+        let path = lock_file("expiry");
+        let a = FileElection::new(&path, Duration::from_millis(50)).unwrap();
+        let b = FileElection::new(&path, Duration::from_millis(50)).unwrap();
+
+        assert!(a.elect());
+        thread::sleep(Duration::from_millis(100));
+
+        assert!(!a.renew(), "an expired lease cannot be renewed");
+        assert!(b.elect());
+        assert!(!a.elect());
+        assert!(!a.renew());
+    }
+
+    #[test]
+    fn renew_extends_lease() {
+        let path = lock_file("renew");
+        let a = FileElection::new(&path, Duration::from_millis(200)).unwrap();
+        let b = FileElection::new(&path, Duration::from_millis(200)).unwrap();
+
+        assert!(a.elect());
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(80));
+            assert!(a.renew());
+            assert!(!b.elect());
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Health, readiness and role reporting for orchestrators and load balancers.
+//!
+//! Enabled with the `health` feature. [`Health`] is an [`Observer`] that
+//! tracks the phase of the loop it is attached to. Clones share that phase,
+//! so one can be passed to the loop while another is queried from other
+//! threads or served over HTTP by [`Health::serve`]:
+//!
+//! - `GET /health` answers `200` while the process is serving requests, and
+//!   `503` once the loop has [stopped](Status::Stopped). A sequencer that
+//!   loses its lease under [`LostLease::Exit`] exits instead, but one that
+//!   steps down, is shut down or runs under [`LostLease::Stop`] returns
+//!   while the process may live on, so orchestrators should restart it.
+//! - `GET /ready` answers `200` once the process is ready for traffic and
+//!   `503` before then or once stopped: a sequencer while activated, a
+//!   consumer once caught up. Load balancers that route commands by
+//!   readiness reach only the active sequencer. A consumer with a
+//!   [watchdog] is not ready while the leader is stale.
+//! - `GET /role` answers with the current [`Status`], as returned by
+//!   [`Status::as_str`].
+//!
+//! [watchdog]: crate::consumer::ConsumerBuilder::watchdog
+//! [`LostLease::Exit`]: crate::sequencer::LostLease::Exit
+//! [`LostLease::Stop`]: crate::sequencer::LostLease::Stop
+//!
+//! ```ignore
+//! let health = Health::sequencer();
+//! let listener = TcpListener::bind("0.0.0.0:8080")?;
+//! thread::spawn({
+//!     let health = health.clone();
+//!     move || health.serve(listener)
+//! });
+//!
+//! SequencerBuilder::new()
+//!     .observer(health)
+//!     .run(&stream, &producer, &inbox, &election, logic)?;
+//! ```
+
+use crate::{
+    consumer::Liveness,
+    http::{self, Response},
+    observer::Observer,
+    sequencer::Status,
+};
+
+use std::{
+    io,
+    net::TcpListener,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+};
+
+struct Inner {
+    status: AtomicUsize,
+    ready: Status,
+    stale: AtomicBool,
+}
+
+/// A shared view of a loop's phase.
+///
+/// A consumer only ever reports [`Status::Starting`] and
+/// [`Status::CaughtUp`]; a sequencer moves through every phase, ending in
+/// [`Status::Stopped`] once its run returns.
+#[derive(Clone)]
+pub struct Health {
+    inner: Arc<Inner>,
+}
+
+impl Health {
+    /// Creates a handle for a [`SequencerBuilder`] observer, ready once the
+    /// sequencer is [activated](Status::Activated).
+    ///
+    /// [`SequencerBuilder`]: crate::sequencer::SequencerBuilder
+    pub fn sequencer() -> Self {
+        Self::new(Status::Activated)
+    }
+
+    /// Creates a handle for a [`ConsumerBuilder`] observer, ready once the
+    /// consumer has [caught up](Status::CaughtUp).
+    ///
+    /// [`ConsumerBuilder`]: crate::consumer::ConsumerBuilder
+    pub fn consumer() -> Self {
+        Self::new(Status::CaughtUp)
+    }
+
+    fn new(ready: Status) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                status: AtomicUsize::new(Status::Starting.to_usize()),
+                ready,
+                stale: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Returns the current phase.
+    pub fn status(&self) -> Status {
+        Status::from_usize(self.inner.status.load(Ordering::Relaxed))
+    }
+
+    /// Returns `true` if the loop is caught up with the stream, whether or
+    /// not it has gone on to lead.
+    pub fn is_caught_up(&self) -> bool {
+        self.status() != Status::Starting
+    }
+
+    /// Returns `true` if the consumer's watchdog reports the leader stale.
+    pub fn is_stale(&self) -> bool {
+        self.inner.stale.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` unless the loop has stopped.
+    pub fn is_alive(&self) -> bool {
+        self.status() != Status::Stopped
+    }
+
+    /// Returns `true` if the process is ready for traffic, which it never
+    /// is once stopped.
+    pub fn is_ready(&self) -> bool {
+        self.status() == self.inner.ready && !self.is_stale()
+    }
+
+    /// Serves `/health`, `/ready` and `/role` over HTTP on `listener`.
+    /// Blocks forever.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        http::serve(listener, |path| {
+            let status = self.status().as_str();
+            match path {
+                "/health" if self.is_alive() => Response::ok("text/plain", "ok\n".to_string()),
+                "/health" => Response::unavailable(format!("{status}\n")),
+                "/ready" if self.is_ready() => Response::ok("text/plain", format!("{status}\n")),
+                "/ready" if self.is_stale() => Response::unavailable("stale\n".to_string()),
+                "/ready" => Response::unavailable(format!("{status}\n")),
+                "/role" => Response::ok("text/plain", format!("{status}\n")),
+                _ => Response::not_found(),
+            }
+        })
+    }
+}
+
+impl Observer for Health {
+    fn status(&self, _from: Status, to: Status) {
+        self.inner.status.store(to.to_usize(), Ordering::Relaxed);
+    }
+
+    fn caught_up(&self) {
+        let _ = self.inner.status.compare_exchange(
+            Status::Starting.to_usize(),
+            Status::CaughtUp.to_usize(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    fn liveness(&self, liveness: Liveness) {
+        self.inner
+            .stale
+            .store(liveness != Liveness::Live, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,114 @@
+//! A minimal HTTP/1.1 server for the operational endpoints.
+//!
+//! Serves one `GET` request per connection and closes it, which is all that
+//! scrapers and orchestrator probes need.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+/// How long a connection may take to send its request, so that idle or
+/// slow clients cannot hold a thread forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Delay after a failed accept, so persistent failures do not spin.
+const ACCEPT_DELAY: Duration = Duration::from_millis(100);
+
+/// A response to a single request.
+pub(crate) struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    /// A `200 OK` response.
+    pub(crate) fn ok(content_type: &'static str, body: String) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    /// A `503 Service Unavailable` response.
+    #[cfg_attr(not(feature = "health"), allow(dead_code))]
+    pub(crate) fn unavailable(body: String) -> Self {
+        Self {
+            status: "503 Service Unavailable",
+            content_type: "text/plain",
+            body,
+        }
+    }
+
+    /// A `404 Not Found` response.
+    pub(crate) fn not_found() -> Self {
+        Self {
+            status: "404 Not Found",
+            content_type: "text/plain",
+            body: "not found\n".to_string(),
+        }
+    }
+}
+
+/// Accepts connections on `listener`, answering `GET` requests with `route`
+/// called on the request path.
+///
+/// Failing to accept a connection, such as when the process runs out of
+/// file descriptors, is logged to stderr and does not stop the server, so
+/// this blocks forever.
+pub(crate) fn serve<F>(listener: TcpListener, route: F) -> io::Result<()>
+where
+    F: Fn(&str) -> Response + Sync,
+{
+    thread::scope(|s| {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("evcore: failed to accept connection: {e}");
+                    thread::sleep(ACCEPT_DELAY);
+                    continue;
+                }
+            };
+            let route = &route;
+            s.spawn(move || handle(stream, route));
+        }
+        Ok(())
+    })
+}
+
+fn handle<F>(mut stream: TcpStream, route: F) -> io::Result<()>
+where
+    F: Fn(&str) -> Response,
+{
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    // Drain the headers; the request has no body.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => route(path),
+        _ => Response::not_found(),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body,
+    )?;
+    stream.flush()
+}
@@ -14,6 +14,12 @@ use crate::Receiver;
 pub trait Inbox: Sender + Receiver + Sync {
     /// Clears the inbox.
     fn clear(&self);
+
+    /// Returns the number of commands waiting to be received, or `None` if
+    /// the backend cannot tell.
+    fn depth(&self) -> Option<usize> {
+        None
+    }
 }
 
 /// Client-side handle for submitting commands to a sequencer's inbox.
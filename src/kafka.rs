@@ -0,0 +1,184 @@
+//! Kafka-backed implementations of [`Stream`] and [`Producer`].
+//!
+//! Enabled with the `kafka` feature. The stream is a single partition of a
+//! topic, which must already exist. For publishes to be durable, the topic
+//! should be replicated with `min.insync.replicas` of at least two.
+
+use crate::{
+    Receiver,
+    stream::{Producer, Stream},
+};
+
+use rdkafka::{
+    ClientConfig, Message, Offset, TopicPartitionList,
+    consumer::{BaseConsumer, Consumer},
+    error::{KafkaResult, RDKafkaErrorCode},
+    producer::{FutureProducer, FutureRecord, Producer as _},
+};
+
+use std::{thread, time::Duration};
+
+/// Delay between attempts when the broker is unreachable.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a single consumer poll blocks before polling again.
+const POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// [`Stream`] backed by a single Kafka partition.
+///
+/// Offsets are Kafka offsets within the partition. Subscriptions assign the
+/// partition directly rather than joining a consumer group, so no offsets are
+/// committed to the broker; resuming is driven entirely by [`Logic::load`].
+///
+/// Subscribing at an offset that retention has already removed is an error
+/// rather than a jump to another offset, since skipping events would break
+/// ordered delivery of every event.
+///
+/// [`Logic::load`]: crate::logic::Logic::load
+pub struct KafkaStream {
+    config: ClientConfig,
+    topic: String,
+    partition: i32,
+}
+
+impl KafkaStream {
+    /// Creates a stream over partition `0` of `topic` on the cluster reachable
+    /// through `brokers`, a comma-separated list of `host:port` pairs.
+    pub fn new(brokers: &str, topic: &str) -> Self {
+        Self::with_partition(brokers, topic, 0)
+    }
+
+    /// Creates a stream over the given partition of `topic`.
+    pub fn with_partition(brokers: &str, topic: &str, partition: i32) -> Self {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+
+        Self {
+            config,
+            topic: topic.to_owned(),
+            partition,
+        }
+    }
+
+    /// Creates a producer that appends to this stream.
+    ///
+    /// The producer is idempotent and waits for `acks=all`, so the broker
+    /// neither acknowledges a write until every in-sync replica has it nor
+    /// duplicates a write that librdkafka retries internally.
+    pub fn producer(&self) -> KafkaResult<KafkaProducer> {
+        let producer = self
+            .config
+            .clone()
+            .set("acks", "all")
+            .set("enable.idempotence", "true")
+            .set("max.in.flight.requests.per.connection", "5")
+            .create()?;
+
+        Ok(KafkaProducer {
+            producer,
+            topic: self.topic.clone(),
+            partition: self.partition,
+        })
+    }
+
+    fn consumer(&self, offset: u64) -> KafkaResult<BaseConsumer> {
+        let consumer: BaseConsumer = self
+            .config
+            .clone()
+            .set("group.id", "evcore")
+            .set("enable.auto.commit", "false")
+            .set("enable.auto.offset.store", "false")
+            .set("auto.offset.reset", "error")
+            .create()?;
+
+        let mut assignment = TopicPartitionList::new();
+        assignment.add_partition_offset(
+            &self.topic,
+            self.partition,
+            Offset::Offset(offset as i64),
+        )?;
+        consumer.assign(&assignment)?;
+
+        Ok(consumer)
+    }
+}
+
+impl Stream for KafkaStream {
+    type Receiver = KafkaReceiver;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        loop {
+            match self.consumer(offset) {
+                Ok(consumer) => return KafkaReceiver { consumer },
+                Err(_) => thread::sleep(RETRY_DELAY),
+            }
+        }
+    }
+}
+
+/// Receiver polling a single assigned Kafka partition.
+///
+/// librdkafka reconnects and refetches internally, so transient errors
+/// reported by a poll are skipped. The receiver panics on a fatal error, or
+/// if the next offset is out of range, such as when retention has removed
+/// it, since no event can be delivered in order after either.
+pub struct KafkaReceiver {
+    consumer: BaseConsumer,
+}
+
+impl Receiver for KafkaReceiver {
+    fn recv(&self) -> Vec<u8> {
+        loop {
+            match self.consumer.poll(POLL_TIMEOUT) {
+                Some(Ok(message)) => return message.payload().unwrap_or_default().to_vec(),
+                Some(Err(e)) => {
+                    if let Some((code, reason)) = self.consumer.client().fatal_error() {
+                        panic!("kafka consumer failed: {code:?}: {reason}");
+                    }
+                    if let Some(
+                        RDKafkaErrorCode::AutoOffsetReset | RDKafkaErrorCode::OffsetOutOfRange,
+                    ) = e.rdkafka_error_code()
+                    {
+                        panic!("kafka consumer failed: {e}");
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// [`Producer`] writing to a single Kafka partition with `acks=all`.
+///
+/// Each publish blocks until the broker acknowledges the write. Failed
+/// deliveries are retried; if the idempotent producer reports a fatal error,
+/// ordering and exactly-once guarantees can no longer be upheld and the
+/// producer panics.
+pub struct KafkaProducer {
+    producer: FutureProducer,
+    topic: String,
+    partition: i32,
+}
+
+impl Producer for KafkaProducer {
+    fn publish(&self, data: &[u8]) {
+        loop {
+            let record = FutureRecord::<(), [u8]>::to(&self.topic)
+                .partition(self.partition)
+                .payload(data);
+
+            let delivered = match self.producer.send_result(record) {
+                Ok(delivery) => matches!(futures::executor::block_on(delivery), Ok(Ok(_))),
+                Err(_) => false,
+            };
+            if delivered {
+                return;
+            }
+
+            if let Some((code, reason)) = self.producer.client().fatal_error() {
+                panic!("kafka producer failed: {code:?}: {reason}");
+            }
+            thread::sleep(RETRY_DELAY);
+        }
+    }
+}
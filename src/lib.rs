@@ -1,11 +1,48 @@
 //! Core abstractions for building event-driven architectures.
 
+pub mod clock;
 pub mod consumer;
 pub mod election;
+pub mod file;
 pub mod inbox;
 pub mod sequencer;
 pub mod stream;
 pub mod logic;
+pub mod observer;
+
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+#[cfg(feature = "fault")]
+pub mod fault;
+#[cfg(feature = "health")]
+pub mod health;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "raft")]
+pub mod raft;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "replay")]
+pub mod replay;
+#[cfg(feature = "replication")]
+pub mod replication;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+
+#[cfg(any(feature = "health", feature = "metrics"))]
+mod http;
+#[cfg(any(feature = "fault", feature = "sim", feature = "testkit"))]
+mod rng;
 
 pub use election::Election;
 pub use inbox::{Inbox, Sender};
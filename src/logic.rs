@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 /// Core event-handling logic for stream processing.
 ///
 /// This trait defines the essential operations for processing events: loading
@@ -30,4 +32,52 @@ pub trait Logic {
     /// The criteria for being caught up is determined by the implementation.
     /// Typical criteria would be to compare physical time against event timestamps.
     fn caught_up(&mut self) -> bool;
-}
\ No newline at end of file
+
+    /// Returns a hash of the current state, or `None` if hashing is not
+    /// supported.
+    ///
+    /// Used to detect divergence between a consumer and the sequencer that
+    /// produced the stream. The hash must depend only on state rebuilt from
+    /// the stream, so that every instance replaying the same events agrees.
+    fn state_hash(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the state hash recorded by a checksum event, or `None` if the
+    /// event is not a checksum event.
+    ///
+    /// Checksum events are published by the sequencer (see
+    /// [`Sequencer::checksum`]) and compared against [`state_hash`] before
+    /// being passed to [`step`], which should leave state unchanged.
+    ///
+    /// [`Sequencer::checksum`]: crate::sequencer::Sequencer::checksum
+    /// [`state_hash`]: Logic::state_hash
+    /// [`step`]: Logic::step
+    fn read_checksum(&self, _event: &[u8]) -> Option<u64> {
+        None
+    }
+
+    /// Returns the timestamp recorded by a heartbeat event, as a time since
+    /// the Unix epoch, or `None` if the event is not a heartbeat event.
+    ///
+    /// Heartbeat events are published by the activated sequencer (see
+    /// [`Sequencer::stream_heartbeat`]) and passed to [`step`], which should
+    /// leave state unchanged. Consumers use them to tell how recently the
+    /// leader was alive.
+    ///
+    /// [`Sequencer::stream_heartbeat`]: crate::sequencer::Sequencer::stream_heartbeat
+    /// [`step`]: Logic::step
+    fn read_heartbeat(&self, _event: &[u8]) -> Option<Duration> {
+        None
+    }
+
+    /// Persists a snapshot of the current state, from which [`load`] can
+    /// later resume.
+    ///
+    /// Called on request through [`SequencerHandle::snapshot`]. Does nothing
+    /// by default.
+    ///
+    /// [`load`]: Logic::load
+    /// [`SequencerHandle::snapshot`]: crate::sequencer::SequencerHandle::snapshot
+    fn save_snapshot(&mut self) {}
+}
@@ -0,0 +1,487 @@
+//! Prometheus metrics for the sequencer and consumer loops.
+//!
+//! Enabled with the `metrics` feature. [`Metrics`] is an [`Observer`] that
+//! aggregates what it sees into counters, gauges and histograms, rendered in
+//! the Prometheus text exposition format by [`Metrics::render`] or served
+//! over HTTP by [`Metrics::serve`]:
+//!
+//! ```ignore
+//! let metrics = Metrics::new();
+//! let listener = TcpListener::bind("127.0.0.1:9464")?;
+//! thread::spawn({
+//!     let metrics = metrics.clone();
+//!     move || metrics.serve(listener)
+//! });
+//!
+//! SequencerBuilder::new()
+//!     .observer(metrics)
+//!     .run(&stream, &producer, &inbox, &election, logic)?;
+//! ```
+
+use crate::{
+    consumer::Liveness,
+    http::{self, Response},
+    observer::Observer,
+    sequencer::Status,
+};
+
+use std::{
+    fmt::Write as _,
+    io,
+    net::TcpListener,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 14] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+const STATUSES: [Status; 5] = [
+    Status::Starting,
+    Status::CaughtUp,
+    Status::Leader,
+    Status::Activated,
+    Status::Stopped,
+];
+
+/// A latency histogram with fixed buckets.
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+
+        // Saturate rather than wrap, so that a huge outlier cannot turn the
+        // sum small again.
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self
+            .sum_nanos
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+                Some(sum.saturating_add(nanos))
+            });
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "histogram");
+
+        let mut cumulative = 0;
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed));
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum {}", sum.as_secs_f64());
+        let _ = writeln!(out, "{name}_count {count}");
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    events_applied: AtomicU64,
+    commands_processed: AtomicU64,
+    commands_rejected: AtomicU64,
+    events_published: AtomicU64,
+    elections_won: AtomicU64,
+    elections_lost: AtomicU64,
+    renewals_succeeded: AtomicU64,
+    renewals_failed: AtomicU64,
+    status: AtomicUsize,
+    inbox_depth: AtomicU64,
+    consumer_lag: AtomicU64,
+    last_step: AtomicU64,
+    last_heartbeat: AtomicU64,
+    leader_stale: AtomicBool,
+    step_duration: Histogram,
+    process_duration: Histogram,
+    publish_latency: Histogram,
+    command_latency: Histogram,
+}
+
+/// An [`Observer`] that aggregates runner activity into Prometheus metrics.
+///
+/// Cloning is cheap and every clone shares the same metrics, so one clone can
+/// be passed to a runner while another serves them.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Inner>,
+}
+
+impl Metrics {
+    /// Creates a set of metrics with every value at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many events the consumer is behind the stream head.
+    ///
+    /// Runners cannot know the head of an arbitrary stream, so applications
+    /// that can measure lag report it here.
+    pub fn set_consumer_lag(&self, events: u64) {
+        self.inner.consumer_lag.store(events, Ordering::Relaxed);
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let inner = &*self.inner;
+        let mut out = String::new();
+
+        counter(
+            &mut out,
+            "evcore_events_applied_total",
+            "Events passed to Logic::step.",
+            &inner.events_applied,
+        );
+        counter(
+            &mut out,
+            "evcore_commands_processed_total",
+            "Commands that produced an event.",
+            &inner.commands_processed,
+        );
+        counter(
+            &mut out,
+            "evcore_commands_rejected_total",
+            "Commands rejected by Sequencer::process.",
+            &inner.commands_rejected,
+        );
+        counter(
+            &mut out,
+            "evcore_events_published_total",
+            "Events durably published by the sequencer.",
+            &inner.events_published,
+        );
+
+        header(
+            &mut out,
+            "evcore_elections_total",
+            "Attempts to acquire leadership.",
+            "counter",
+        );
+        labelled(
+            &mut out,
+            "evcore_elections_total",
+            "result",
+            "won",
+            &inner.elections_won,
+        );
+        labelled(
+            &mut out,
+            "evcore_elections_total",
+            "result",
+            "lost",
+            &inner.elections_lost,
+        );
+
+        header(
+            &mut out,
+            "evcore_renewals_total",
+            "Attempts to renew the leadership lease.",
+            "counter",
+        );
+        labelled(
+            &mut out,
+            "evcore_renewals_total",
+            "result",
+            "renewed",
+            &inner.renewals_succeeded,
+        );
+        labelled(
+            &mut out,
+            "evcore_renewals_total",
+            "result",
+            "failed",
+            &inner.renewals_failed,
+        );
+
+        header(
+            &mut out,
+            "evcore_sequencer_status",
+            "Current sequencer phase; 1 for the active phase.",
+            "gauge",
+        );
+        let current = Status::from_usize(inner.status.load(Ordering::Relaxed));
+        for status in STATUSES {
+            let _ = writeln!(
+                out,
+                "evcore_sequencer_status{{status=\"{}\"}} {}",
+                status.as_str(),
+                (status == current) as u8,
+            );
+        }
+
+        gauge(
+            &mut out,
+            "evcore_inbox_depth",
+            "Commands waiting in the inbox.",
+            inner.inbox_depth.load(Ordering::Relaxed) as f64,
+        );
+        gauge(
+            &mut out,
+            "evcore_consumer_lag_events",
+            "Events between the consumer and the stream head.",
+            inner.consumer_lag.load(Ordering::Relaxed) as f64,
+        );
+        gauge(
+            &mut out,
+            "evcore_last_step_timestamp_seconds",
+            "Unix time of the last event passed to Logic::step.",
+            Duration::from_nanos(inner.last_step.load(Ordering::Relaxed)).as_secs_f64(),
+        );
+
+        gauge(
+            &mut out,
+            "evcore_leader_heartbeat_timestamp_seconds",
+            "Unix time recorded by the last heartbeat event read from the stream.",
+            Duration::from_nanos(inner.last_heartbeat.load(Ordering::Relaxed)).as_secs_f64(),
+        );
+        gauge(
+            &mut out,
+            "evcore_leader_stale",
+            "1 while the consumer watchdog reports no events from the leader.",
+            u8::from(inner.leader_stale.load(Ordering::Relaxed)) as f64,
+        );
+
+        inner.step_duration.render(
+            &mut out,
+            "evcore_step_duration_seconds",
+            "Time spent in Logic::step.",
+        );
+        inner.process_duration.render(
+            &mut out,
+            "evcore_process_duration_seconds",
+            "Time spent in Sequencer::process.",
+        );
+        inner.publish_latency.render(
+            &mut out,
+            "evcore_publish_latency_seconds",
+            "Time for the producer to acknowledge a publish.",
+        );
+        inner.command_latency.render(
+            &mut out,
+            "evcore_command_to_event_seconds",
+            "Time from receiving a command to publishing its event.",
+        );
+
+        out
+    }
+
+    /// Serves the metrics over HTTP on `listener`, answering `GET /metrics`.
+    /// Blocks forever.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        http::serve(listener, |path| match path {
+            "/metrics" => Response::ok("text/plain; version=0.0.4", self.render()),
+            _ => Response::not_found(),
+        })
+    }
+}
+
+impl Observer for Metrics {
+    fn status(&self, _from: Status, to: Status) {
+        self.inner.status.store(to.to_usize(), Ordering::Relaxed);
+    }
+
+    fn elect(&self, won: bool) {
+        match won {
+            true => &self.inner.elections_won,
+            false => &self.inner.elections_lost,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn renew(&self, renewed: bool) {
+        match renewed {
+            true => &self.inner.renewals_succeeded,
+            false => &self.inner.renewals_failed,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn step(&self, _event: &[u8], elapsed: Duration) {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+
+        self.inner.events_applied.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .last_step
+            .store(now.as_nanos() as u64, Ordering::Relaxed);
+        self.inner.step_duration.observe(elapsed);
+    }
+
+    fn process(&self, _command: &[u8], event: Option<&[u8]>, elapsed: Duration) {
+        match event {
+            Some(_) => &self.inner.commands_processed,
+            None => &self.inner.commands_rejected,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+        self.inner.process_duration.observe(elapsed);
+    }
+
+    fn publish(&self, _event: &[u8], latency: Duration) {
+        self.inner.events_published.fetch_add(1, Ordering::Relaxed);
+        self.inner.publish_latency.observe(latency);
+    }
+
+    fn committed(&self, _command: &[u8], _event: &[u8], latency: Duration) {
+        self.inner.command_latency.observe(latency);
+    }
+
+    fn heartbeat(&self, timestamp: Duration) {
+        self.inner
+            .last_heartbeat
+            .store(timestamp.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn liveness(&self, liveness: Liveness) {
+        self.inner
+            .leader_stale
+            .store(liveness != Liveness::Live, Ordering::Relaxed);
+    }
+
+    fn inbox_depth(&self, depth: usize) {
+        self.inner
+            .inbox_depth
+            .store(depth as u64, Ordering::Relaxed);
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: &AtomicU64) {
+    header(out, name, help, "counter");
+    let _ = writeln!(out, "{name} {}", value.load(Ordering::Relaxed));
+}
+
+fn labelled(out: &mut String, name: &str, label: &str, value: &str, count: &AtomicU64) {
+    let _ = writeln!(
+        out,
+        "{name}{{{label}=\"{value}\"}} {}",
+        count.load(Ordering::Relaxed)
+    );
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    header(out, name, help, "gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+    };
+
+    /// Returns the value of the sample named `sample` in `text`.
+    fn sample(text: &str, sample: &str) -> f64 {
+        text.lines()
+            .find_map(|line| line.strip_prefix(sample)?.strip_prefix(' '))
+            .unwrap_or_else(|| panic!("no sample {sample}"))
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn serves_metrics_on_localhost() {
+        let metrics = Metrics::new();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn({
+            let metrics = metrics.clone();
+            move || metrics.serve(listener)
+        });
+
+        metrics.elect(false);
+        metrics.elect(true);
+        metrics.renew(true);
+        metrics.status(Status::Leader, Status::Activated);
+        metrics.process(b"a", Some(b"a"), Duration::from_micros(50));
+        metrics.process(b"b", None, Duration::from_millis(3));
+        metrics.publish(b"a", Duration::from_millis(2));
+        for elapsed in [50, 200, 200, 4_000_000] {
+            metrics.step(b"a", Duration::from_micros(elapsed));
+        }
+
+        let mut conn = TcpStream::connect(addr).unwrap();
+        write!(conn, "GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        conn.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+
+        assert_eq!(sample(body, "evcore_events_applied_total"), 4.0);
+        assert_eq!(sample(body, "evcore_commands_processed_total"), 1.0);
+        assert_eq!(sample(body, "evcore_commands_rejected_total"), 1.0);
+        assert_eq!(sample(body, "evcore_events_published_total"), 1.0);
+        assert_eq!(sample(body, "evcore_elections_total{result=\"won\"}"), 1.0);
+        assert_eq!(sample(body, "evcore_elections_total{result=\"lost\"}"), 1.0);
+        assert_eq!(
+            sample(body, "evcore_renewals_total{result=\"renewed\"}"),
+            1.0
+        );
+        assert_eq!(
+            sample(body, "evcore_renewals_total{result=\"failed\"}"),
+            0.0
+        );
+        assert_eq!(
+            sample(body, "evcore_sequencer_status{status=\"activated\"}"),
+            1.0
+        );
+        assert_eq!(
+            sample(body, "evcore_sequencer_status{status=\"starting\"}"),
+            0.0
+        );
+
+        // Buckets are cumulative, and the 4s step falls in +Inf alone.
+        let step = "evcore_step_duration_seconds";
+        assert_eq!(
+            sample(body, &format!("{step}_bucket{{le=\"0.0001\"}}")),
+            1.0
+        );
+        assert_eq!(
+            sample(body, &format!("{step}_bucket{{le=\"0.00025\"}}")),
+            3.0
+        );
+        assert_eq!(sample(body, &format!("{step}_bucket{{le=\"2.5\"}}")), 3.0);
+        assert_eq!(sample(body, &format!("{step}_bucket{{le=\"+Inf\"}}")), 4.0);
+        assert_eq!(sample(body, &format!("{step}_count")), 4.0);
+        assert!((sample(body, &format!("{step}_sum")) - 4.00045).abs() < 1e-9);
+
+        let process = "evcore_process_duration_seconds";
+        assert_eq!(
+            sample(body, &format!("{process}_bucket{{le=\"+Inf\"}}")),
+            sample(body, &format!("{process}_count"))
+        );
+    }
+
+    #[test]
+    fn histogram_sum_saturates() {
+        let histogram = Histogram::default();
+        histogram.observe(Duration::MAX);
+        histogram.observe(Duration::from_secs(1));
+        assert_eq!(histogram.sum_nanos.load(Ordering::Relaxed), u64::MAX);
+        assert_eq!(histogram.count.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,188 @@
+//! NATS JetStream-backed implementations of [`Stream`] and [`Producer`].
+//!
+//! Enabled with the `nats` feature. The JetStream client is asynchronous, so
+//! each [`NatsStream`] owns a small Tokio runtime that its receivers and
+//! producers block on.
+
+use crate::{
+    Receiver,
+    stream::{Producer, Stream},
+};
+
+use async_nats::jetstream::{
+    self, Context,
+    consumer::{DeliverPolicy, pull::Ordered, pull::OrderedConfig},
+};
+use futures::StreamExt;
+use tokio::runtime::{self, Runtime};
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Delay between attempts when the server is unreachable.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// [`Stream`] backed by a JetStream stream capturing a single subject.
+///
+/// Offsets map directly onto stream sequences: the event at offset `n` has
+/// stream sequence `n + 1`, since JetStream sequences start at one. Each
+/// subscription is an ordered consumer, which the client transparently
+/// recreates on gaps or missed heartbeats to preserve ordered delivery.
+#[derive(Clone)]
+pub struct NatsStream {
+    runtime: Arc<Runtime>,
+    context: Context,
+    stream: String,
+    subject: String,
+}
+
+impl NatsStream {
+    /// Connects to the server at `url` and ensures the JetStream stream
+    /// `stream` exists, capturing messages published to `subject`.
+    pub fn connect(url: &str, stream: &str, subject: &str) -> Result<Self, async_nats::Error> {
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+
+        let context = runtime.block_on(async {
+            let client = async_nats::connect(url).await?;
+            let context = jetstream::new(client);
+            context
+                .get_or_create_stream(jetstream::stream::Config {
+                    name: stream.to_owned(),
+                    subjects: vec![subject.to_owned()],
+                    ..Default::default()
+                })
+                .await?;
+
+            Ok::<_, async_nats::Error>(context)
+        })?;
+
+        Ok(Self {
+            runtime: Arc::new(runtime),
+            context,
+            stream: stream.to_owned(),
+            subject: subject.to_owned(),
+        })
+    }
+
+    /// Creates a producer that publishes to this stream's subject.
+    pub fn producer(&self) -> NatsProducer {
+        NatsProducer {
+            runtime: Arc::clone(&self.runtime),
+            context: self.context.clone(),
+            subject: self.subject.clone(),
+        }
+    }
+
+    async fn messages(&self, sequence: u64) -> Result<Ordered, async_nats::Error> {
+        let stream = self.context.get_stream(&self.stream).await?;
+        let consumer = stream
+            .create_consumer(OrderedConfig {
+                deliver_policy: DeliverPolicy::ByStartSequence {
+                    start_sequence: sequence,
+                },
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(consumer.messages().await?)
+    }
+}
+
+impl Stream for NatsStream {
+    type Receiver = NatsReceiver;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        NatsReceiver {
+            stream: self.clone(),
+            state: Mutex::new(ReceiverState {
+                sequence: offset + 1,
+                messages: None,
+            }),
+        }
+    }
+}
+
+struct ReceiverState {
+    sequence: u64,
+    messages: Option<Ordered>,
+}
+
+/// Receiver reading a JetStream stream through an ordered consumer.
+///
+/// If the consumer fails in a way the client cannot recover from, a new one
+/// is created starting at the next undelivered sequence.
+pub struct NatsReceiver {
+    stream: NatsStream,
+    state: Mutex<ReceiverState>,
+}
+
+impl Receiver for NatsReceiver {
+    fn recv(&self) -> Vec<u8> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            let ReceiverState { sequence, messages } = &mut *state;
+            let message = self.stream.runtime.block_on(async {
+                if messages.is_none() {
+                    *messages = Some(self.stream.messages(*sequence).await?);
+                }
+                match messages.as_mut().unwrap().next().await {
+                    Some(message) => Ok(message?),
+                    None => Err("consumer closed".into()),
+                }
+            });
+
+            match message {
+                Ok(message) => {
+                    state.sequence = match message.info() {
+                        Ok(info) => info.stream_sequence + 1,
+                        Err(_) => state.sequence + 1,
+                    };
+                    return message.payload.to_vec();
+                }
+                Err::<_, async_nats::Error>(_) => {
+                    state.messages = None;
+                    thread::sleep(RETRY_DELAY);
+                }
+            }
+        }
+    }
+}
+
+/// [`Producer`] publishing to a JetStream subject.
+///
+/// Each publish waits for the JetStream acknowledgment, which the server sends
+/// once the message is stored according to the stream's replication and
+/// storage settings. A publish whose acknowledgment is lost is retried and may
+/// therefore be stored twice.
+pub struct NatsProducer {
+    runtime: Arc<Runtime>,
+    context: Context,
+    subject: String,
+}
+
+impl Producer for NatsProducer {
+    fn publish(&self, data: &[u8]) {
+        loop {
+            let ack = self.runtime.block_on(async {
+                self.context
+                    .publish(self.subject.clone(), data.to_vec().into())
+                    .await?
+                    .await?;
+
+                Ok::<_, async_nats::Error>(())
+            });
+
+            match ack {
+                Ok(()) => return,
+                Err(_) => thread::sleep(RETRY_DELAY),
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Hooks for observing the sequencer and consumer loops.
+//!
+//! An [`Observer`] is called synchronously from the loops it is passed to,
+//! including the sequencer's election thread, so callbacks should be cheap:
+//! record a metric, write a log line, or hand off to another thread.
+//!
+//! A pair of observers is itself an observer, so several can be attached to
+//! one loop: `&(metrics, health)`.
+
+use crate::{consumer::Liveness, sequencer::Status};
+
+use std::time::Duration;
+
+/// Callbacks invoked by the consumer and sequencer loops, as set with
+/// [`ConsumerBuilder::observer`] and [`SequencerBuilder::observer`].
+///
+/// Every method has an empty default, so implementations only override the
+/// events they care about. `()` is the observer that ignores everything.
+///
+/// [`ConsumerBuilder::observer`]: crate::consumer::ConsumerBuilder::observer
+/// [`SequencerBuilder::observer`]: crate::sequencer::SequencerBuilder::observer
+pub trait Observer: Sync {
+    /// Called when the sequencer moves from one phase to the next.
+    fn status(&self, _from: Status, _to: Status) {}
+
+    /// Called once, when the consumer's logic first reports that it has
+    /// [caught up](crate::logic::Logic::caught_up) with the stream.
+    fn caught_up(&self) {}
+
+    /// Called after each attempt to acquire leadership.
+    fn elect(&self, _won: bool) {}
+
+    /// Called after each attempt to renew the leadership lease.
+    fn renew(&self, _renewed: bool) {}
+
+    /// Called after an event is passed to [`Logic::step`], with the time the
+    /// step took.
+    ///
+    /// [`Logic::step`]: crate::logic::Logic::step
+    fn step(&self, _event: &[u8], _elapsed: Duration) {}
+
+    /// Called after a command is passed to [`Sequencer::process`], with the
+    /// event produced, or `None` if the command was rejected, and the time
+    /// processing took.
+    ///
+    /// [`Sequencer::process`]: crate::sequencer::Sequencer::process
+    fn process(&self, _command: &[u8], _event: Option<&[u8]>, _elapsed: Duration) {}
+
+    /// Called after an event is durably published, with the time the
+    /// producer took to acknowledge it.
+    fn publish(&self, _event: &[u8], _latency: Duration) {}
+
+    /// Called after the event produced by a command is published, with the
+    /// time from receiving the command to the publish being acknowledged.
+    fn committed(&self, _command: &[u8], _event: &[u8], _latency: Duration) {}
+
+    /// Called when the consumer reads a [heartbeat event], with the
+    /// leader's timestamp as a time since the Unix epoch.
+    ///
+    /// [heartbeat event]: crate::logic::Logic::read_heartbeat
+    fn heartbeat(&self, _timestamp: Duration) {}
+
+    /// Called from a consumer's [watchdog] thread when the leader goes
+    /// quiet for longer than the window, and again when events resume.
+    ///
+    /// [watchdog]: crate::consumer::ConsumerBuilder::watchdog
+    fn liveness(&self, _liveness: Liveness) {}
+
+    /// Called after a command is received from the inbox, with the number of
+    /// commands still waiting, if the inbox reports it.
+    fn inbox_depth(&self, _depth: usize) {}
+}
+
+impl Observer for () {}
+
+impl<O: Observer + ?Sized> Observer for &O {
+    fn status(&self, from: Status, to: Status) {
+        (**self).status(from, to);
+    }
+
+    fn caught_up(&self) {
+        (**self).caught_up();
+    }
+
+    fn elect(&self, won: bool) {
+        (**self).elect(won);
+    }
+
+    fn renew(&self, renewed: bool) {
+        (**self).renew(renewed);
+    }
+
+    fn step(&self, event: &[u8], elapsed: Duration) {
+        (**self).step(event, elapsed);
+    }
+
+    fn process(&self, command: &[u8], event: Option<&[u8]>, elapsed: Duration) {
+        (**self).process(command, event, elapsed);
+    }
+
+    fn publish(&self, event: &[u8], latency: Duration) {
+        (**self).publish(event, latency);
+    }
+
+    fn committed(&self, command: &[u8], event: &[u8], latency: Duration) {
+        (**self).committed(command, event, latency);
+    }
+
+    fn heartbeat(&self, timestamp: Duration) {
+        (**self).heartbeat(timestamp);
+    }
+
+    fn liveness(&self, liveness: Liveness) {
+        (**self).liveness(liveness);
+    }
+
+    fn inbox_depth(&self, depth: usize) {
+        (**self).inbox_depth(depth);
+    }
+}
+
+/// Forwards every callback to both observers, first to second.
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    fn status(&self, from: Status, to: Status) {
+        self.0.status(from, to);
+        self.1.status(from, to);
+    }
+
+    fn caught_up(&self) {
+        self.0.caught_up();
+        self.1.caught_up();
+    }
+
+    fn elect(&self, won: bool) {
+        self.0.elect(won);
+        self.1.elect(won);
+    }
+
+    fn renew(&self, renewed: bool) {
+        self.0.renew(renewed);
+        self.1.renew(renewed);
+    }
+
+    fn step(&self, event: &[u8], elapsed: Duration) {
+        self.0.step(event, elapsed);
+        self.1.step(event, elapsed);
+    }
+
+    fn process(&self, command: &[u8], event: Option<&[u8]>, elapsed: Duration) {
+        self.0.process(command, event, elapsed);
+        self.1.process(command, event, elapsed);
+    }
+
+    fn publish(&self, event: &[u8], latency: Duration) {
+        self.0.publish(event, latency);
+        self.1.publish(event, latency);
+    }
+
+    fn committed(&self, command: &[u8], event: &[u8], latency: Duration) {
+        self.0.committed(command, event, latency);
+        self.1.committed(command, event, latency);
+    }
+
+    fn heartbeat(&self, timestamp: Duration) {
+        self.0.heartbeat(timestamp);
+        self.1.heartbeat(timestamp);
+    }
+
+    fn liveness(&self, liveness: Liveness) {
+        self.0.liveness(liveness);
+        self.1.liveness(liveness);
+    }
+
+    fn inbox_depth(&self, depth: usize) {
+        self.0.inbox_depth(depth);
+        self.1.inbox_depth(depth);
+    }
+}
@@ -0,0 +1,285 @@
+//! PostgreSQL-backed implementations of [`Stream`], [`Producer`] and [`Election`].
+//!
+//! Enabled with the `postgres` feature. Together these allow a complete
+//! deployment on a single database: events are rows in a table, and
+//! leadership is a session-level advisory lock.
+//!
+//! Table names are quoted as SQL identifiers wherever they are used, so
+//! they are case-sensitive and may not be schema-qualified.
+
+use crate::{
+    Receiver,
+    election::Election,
+    stream::{Producer, Stream},
+};
+
+use ::postgres::{Client, Error, NoTls, fallible_iterator::FallibleIterator};
+
+use std::{collections::VecDeque, sync::Mutex, thread, time::Duration};
+
+/// Delay between attempts when the database is unreachable.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a receiver waits for a notification before polling the table.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum number of rows fetched by a single poll.
+const READ_COUNT: i64 = 1024;
+
+/// A lazily (re)established connection to a PostgreSQL server.
+///
+/// Any query error drops the connection so the next call reconnects. The
+/// optional `setup` statements are executed on every new connection.
+struct Conn {
+    params: String,
+    setup: Option<String>,
+    client: Mutex<Option<Client>>,
+}
+
+impl Conn {
+    fn new(params: &str, setup: Option<String>) -> Self {
+        Self {
+            params: params.to_owned(),
+            setup,
+            client: Mutex::new(None),
+        }
+    }
+
+    fn connect(&self) -> Result<Client, Error> {
+        let mut client = Client::connect(&self.params, NoTls)?;
+        if let Some(setup) = &self.setup {
+            client.batch_execute(setup)?;
+        }
+        Ok(client)
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Client) -> Result<T, Error>) -> Result<T, Error> {
+        let mut guard = self.client.lock().unwrap();
+        let client = match guard.as_mut() {
+            Some(client) => client,
+            None => guard.insert(self.connect()?),
+        };
+
+        let result = f(client);
+        if result.is_err() {
+            *guard = None;
+        }
+        result
+    }
+
+    /// Runs `f` until it succeeds, reconnecting between attempts.
+    fn retry<T>(&self, mut f: impl FnMut(&mut Client) -> Result<T, Error>) -> T {
+        loop {
+            match self.with(&mut f) {
+                Ok(value) => return value,
+                Err(_) => thread::sleep(RETRY_DELAY),
+            }
+        }
+    }
+}
+
+/// Quotes `name` as an SQL identifier, so it is used exactly as given.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// [`Stream`] backed by an append-only events table.
+///
+/// Offsets are the table's `bigserial` primary key, so the first event has
+/// offset `1` and [`subscribe`](Stream::subscribe) delivers every event whose
+/// offset is at or after the given one. Receivers tail the table by polling,
+/// woken early by a `NOTIFY` on a channel named after the table.
+pub struct PostgresStream {
+    params: String,
+    table: String,
+}
+
+impl PostgresStream {
+    /// Connects with the given connection string and creates `table` if it
+    /// does not already exist.
+    pub fn new(params: &str, table: &str) -> Result<Self, Error> {
+        let mut client = Client::connect(params, NoTls)?;
+        client.batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (id BIGSERIAL PRIMARY KEY, data BYTEA NOT NULL)",
+            quote(table)
+        ))?;
+
+        Ok(Self {
+            params: params.to_owned(),
+            table: table.to_owned(),
+        })
+    }
+
+    /// Creates a producer that appends to this stream.
+    pub fn producer(&self) -> PostgresProducer {
+        PostgresProducer {
+            conn: Conn::new(&self.params, None),
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl Stream for PostgresStream {
+    type Receiver = PostgresReceiver;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        PostgresReceiver {
+            conn: Conn::new(&self.params, Some(format!("LISTEN {}", quote(&self.table)))),
+            table: self.table.clone(),
+            state: Mutex::new(ReceiverState {
+                next: offset as i64,
+                buffer: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+struct ReceiverState {
+    next: i64,
+    buffer: VecDeque<Vec<u8>>,
+}
+
+/// Receiver tailing an events table.
+pub struct PostgresReceiver {
+    conn: Conn,
+    table: String,
+    state: Mutex<ReceiverState>,
+}
+
+impl Receiver for PostgresReceiver {
+    fn recv(&self) -> Vec<u8> {
+        let mut state = self.state.lock().unwrap();
+        let query = format!(
+            "SELECT id, data FROM {} WHERE id >= $1 ORDER BY id LIMIT $2",
+            quote(&self.table)
+        );
+
+        while state.buffer.is_empty() {
+            let rows = self.conn.retry(|client| {
+                let rows = client.query(&query, &[&state.next, &READ_COUNT])?;
+                if rows.is_empty() {
+                    // Drain any notification that arrived since the last
+                    // poll, or wait for the next one.
+                    let mut notifications = client.notifications();
+                    notifications.timeout_iter(POLL_INTERVAL).next()?;
+                    while notifications.iter().next()?.is_some() {}
+                }
+                Ok(rows)
+            });
+
+            for row in rows {
+                state.next = row.get::<_, i64>(0) + 1;
+                state.buffer.push_back(row.get(1));
+            }
+        }
+
+        state.buffer.pop_front().unwrap()
+    }
+}
+
+/// [`Producer`] inserting each event in its own committed transaction.
+///
+/// Each insert takes an `EXCLUSIVE` lock on the table for the duration of its
+/// transaction. This serializes writers so that offsets become visible in
+/// order and a tailing receiver never skips a row committed late. A publish
+/// whose commit outcome is lost to a connection failure is retried and may
+/// therefore be stored twice.
+pub struct PostgresProducer {
+    conn: Conn,
+    table: String,
+}
+
+impl Producer for PostgresProducer {
+    fn publish(&self, data: &[u8]) {
+        let table = quote(&self.table);
+        let lock = format!("LOCK TABLE {table} IN EXCLUSIVE MODE");
+        let insert = format!("INSERT INTO {table} (data) VALUES ($1)");
+
+        self.conn.retry(|client| {
+            let mut transaction = client.transaction()?;
+            transaction.batch_execute(&lock)?;
+            transaction.execute(&insert, &[&data])?;
+            transaction.execute("SELECT pg_notify($1, '')", &[&self.table])?;
+            transaction.commit()
+        });
+    }
+}
+
+/// [`Election`] based on a session-level advisory lock.
+///
+/// The lock is held for as long as the candidate's database session lives,
+/// so [`renew`](Election::renew) simply confirms the session still holds it.
+/// If the connection drops, the server releases the lock and renewal fails
+/// without reconnecting. A standby takes over once the server notices the
+/// leader's session is gone, which can be bounded with the `keepalives_idle`
+/// and `tcp_user_timeout` connection parameters.
+///
+/// The lock records no identity, so [`leader`](Election::leader) is not
+/// reported.
+pub struct PostgresElection {
+    conn: Conn,
+    key: i64,
+}
+
+impl PostgresElection {
+    /// Creates a candidate competing for the advisory lock `key`.
+    pub fn new(params: &str, key: i64) -> Self {
+        Self {
+            conn: Conn::new(params, None),
+            key,
+        }
+    }
+}
+
+impl Election for PostgresElection {
+    fn elect(&self) -> bool {
+        if self.renew() {
+            return true;
+        }
+
+        self.conn
+            .with(|client| {
+                let row = client.query_one("SELECT pg_try_advisory_lock($1)", &[&self.key])?;
+                row.try_get::<_, bool>(0)
+            })
+            .unwrap_or(false)
+    }
+
+    fn renew(&self) -> bool {
+        let mut guard = self.conn.client.lock().unwrap();
+        let Some(client) = guard.as_mut() else {
+            return false;
+        };
+
+        // A bigint advisory key is split across `classid` (high 32 bits) and
+        // `objid` (low 32 bits), with `objsubid` set to 1.
+        let held = client.query_one(
+            "SELECT EXISTS (
+                SELECT 1 FROM pg_locks
+                WHERE locktype = 'advisory' AND granted AND objsubid = 1
+                  AND pid = pg_backend_pid()
+                  AND ((classid::bigint << 32) | objid::bigint) = $1
+            )",
+            &[&self.key],
+        );
+
+        match held.and_then(|row| row.try_get::<_, bool>(0)) {
+            Ok(held) => held,
+            Err(_) => {
+                *guard = None;
+                false
+            }
+        }
+    }
+
+    fn resign(&self) {
+        let mut guard = self.conn.client.lock().unwrap();
+        if let Some(client) = guard.as_mut()
+            && client
+                .execute("SELECT pg_advisory_unlock($1)", &[&self.key])
+                .is_err()
+        {
+            *guard = None;
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! Durable storage for a Raft node: the replicated log and the voting state.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// Size of a record header: term (8 bytes), kind (1 byte), length (4 bytes).
+const HEADER: u64 = 13;
+
+/// Largest event an entry may carry, guarding against corrupt lengths.
+pub(super) const MAX_DATA: usize = 1 << 20;
+
+const KIND_NOOP: u8 = 0;
+const KIND_DATA: u8 = 1;
+
+/// A single log entry.
+///
+/// Entries without data are no-ops appended by a new leader to commit
+/// entries from earlier terms. They occupy an offset but are not delivered
+/// to subscribers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Entry {
+    pub term: u64,
+    pub data: Option<Vec<u8>>,
+}
+
+impl Entry {
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let (kind, data) = match &self.data {
+            Some(data) => (KIND_DATA, data.as_slice()),
+            None => (KIND_NOOP, &[][..]),
+        };
+        w.write_all(&self.term.to_le_bytes())?;
+        w.write_all(&[kind])?;
+        w.write_all(&(data.len() as u32).to_le_bytes())?;
+        w.write_all(data)
+    }
+
+    pub fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let mut header = [0; HEADER as usize];
+        r.read_exact(&mut header)?;
+
+        let term = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let len = u32::from_le_bytes(header[9..].try_into().unwrap()) as usize;
+        let kind = header[8];
+        if len > MAX_DATA || (kind != KIND_DATA && kind != KIND_NOOP) {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let mut data = vec![0; len];
+        r.read_exact(&mut data)?;
+        let data = (kind == KIND_DATA).then_some(data);
+        Ok(Self { term, data })
+    }
+
+    fn size(&self) -> u64 {
+        HEADER + self.data.as_ref().map_or(0, Vec::len) as u64
+    }
+}
+
+/// Append-only file of entries, mirrored in memory.
+///
+/// Every mutation is synced to disk before returning, so an entry a node
+/// acknowledges survives a crash. A torn record at the tail, left by a crash
+/// mid-write, is discarded on open; any other malformed record fails it.
+pub(super) struct Log {
+    file: File,
+    entries: Vec<Entry>,
+    positions: Vec<u64>,
+    end: u64,
+}
+
+impl Log {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut entries = Vec::new();
+        let mut positions = Vec::new();
+        let mut end = 0;
+        let mut reader = BufReader::new(&mut file);
+        loop {
+            let entry = match Entry::read_from(&mut reader) {
+                Ok(entry) => entry,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+            positions.push(end);
+            end += entry.size();
+            entries.push(entry);
+        }
+
+        file.set_len(end)?;
+        file.seek(SeekFrom::Start(end))?;
+
+        Ok(Self {
+            file,
+            entries,
+            positions,
+            end,
+        })
+    }
+
+    /// Returns the number of entries in the log.
+    pub fn len(&self) -> u64 {
+        self.entries.len() as u64
+    }
+
+    /// Returns the entry at `index`.
+    pub fn get(&self, index: u64) -> &Entry {
+        &self.entries[index as usize]
+    }
+
+    /// Returns the term of the entry preceding `len`, or `0` if `len` is zero.
+    pub fn term_before(&self, len: u64) -> u64 {
+        match len {
+            0 => 0,
+            len => self.entries[len as usize - 1].term,
+        }
+    }
+
+    /// Returns the term of the last entry, or `0` if the log is empty.
+    pub fn last_term(&self) -> u64 {
+        self.term_before(self.len())
+    }
+
+    /// Returns up to `max` entries starting at `from`, stopping before the
+    /// records would exceed `max_bytes` unless that leaves none.
+    pub fn slice(&self, from: u64, max: usize, max_bytes: u64) -> Vec<Entry> {
+        let mut bytes = 0;
+        self.entries[from as usize..]
+            .iter()
+            .take(max)
+            .take_while(|entry| {
+                bytes += entry.size();
+                bytes <= max_bytes || bytes == entry.size()
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Appends entries and syncs them to disk.
+    pub fn append(&mut self, entries: &[Entry]) -> io::Result<()> {
+        let mut buf = Vec::new();
+        for entry in entries {
+            self.positions.push(self.end + buf.len() as u64);
+            entry.write_to(&mut buf)?;
+        }
+
+        self.file.write_all(&buf)?;
+        self.file.sync_data()?;
+        self.end += buf.len() as u64;
+        self.entries.extend_from_slice(entries);
+
+        Ok(())
+    }
+
+    /// Removes every entry at or after `len`.
+    pub fn truncate(&mut self, len: u64) -> io::Result<()> {
+        let Some(&end) = self.positions.get(len as usize) else {
+            return Ok(());
+        };
+
+        self.file.set_len(end)?;
+        self.file.seek(SeekFrom::Start(end))?;
+        self.file.sync_data()?;
+        self.end = end;
+        self.entries.truncate(len as usize);
+        self.positions.truncate(len as usize);
+
+        Ok(())
+    }
+}
+
+/// The current term and the candidate voted for in it.
+///
+/// Persisted before a node answers any request in a new term, so a restarted
+/// node never votes twice in the same term.
+pub(super) struct Meta {
+    path: PathBuf,
+    pub term: u64,
+    pub voted_for: Option<usize>,
+}
+
+impl Meta {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let (term, voted_for) = match fs::read(&path) {
+            Ok(bytes) if bytes.len() == 16 => {
+                let term = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+                let vote = u64::from_le_bytes(bytes[8..].try_into().unwrap());
+                (term, vote.checked_sub(1).map(|id| id as usize))
+            }
+            Ok(_) => return Err(io::ErrorKind::InvalidData.into()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (0, None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            path,
+            term,
+            voted_for,
+        })
+    }
+
+    /// Atomically replaces the persisted state with the current fields.
+    pub fn store(&self) -> io::Result<()> {
+        let vote = self.voted_for.map_or(0, |id| id as u64 + 1);
+        let tmp = self.path.with_extension("tmp");
+
+        let mut file = File::create(&tmp)?;
+        file.write_all(&self.term.to_le_bytes())?;
+        file.write_all(&vote.to_le_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+
+        if let Some(dir) = self.path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, process};
+
+    fn log_file(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("evcore-raft-{name}-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn oversized_record_is_rejected() {
+        let mut record = Vec::new();
+        record.extend_from_slice(&1u64.to_le_bytes());
+        record.push(KIND_DATA);
+        record.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        let err = Entry::read_from(&mut &record[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn open_discards_torn_tail() {
+        let path = log_file("torn");
+        let entry = Entry {
+            term: 1,
+            data: Some(b"event".to_vec()),
+        };
+        Log::open(&path)
+            .unwrap()
+            .append(std::slice::from_ref(&entry))
+            .unwrap();
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&2u64.to_le_bytes()).unwrap();
+        drop(file);
+
+        let log = Log::open(&path).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(*log.get(0), entry);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_rejects_corrupt_length() {
+        let path = log_file("corrupt");
+        let mut record = Vec::new();
+        record.extend_from_slice(&1u64.to_le_bytes());
+        record.push(KIND_DATA);
+        record.extend_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &record).unwrap();
+
+        let err = Log::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn slice_respects_byte_budget() {
+        let path = log_file("slice");
+        let mut log = Log::open(&path).unwrap();
+        let entry = Entry {
+            term: 1,
+            data: Some(vec![0; 100]),
+        };
+        log.append(&vec![entry; 10]).unwrap();
+
+        let size = HEADER + 100;
+        assert_eq!(log.slice(0, 10, size * 3).len(), 3);
+        assert_eq!(log.slice(0, 2, size * 3).len(), 2);
+        assert_eq!(log.slice(0, 10, 1).len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! Wire format for Raft RPCs.
+//!
+//! Each message is framed by a little-endian `u32` length, followed by a
+//! one-byte tag and the message fields as little-endian integers.
+
+use super::{MAX_BATCH, MAX_BATCH_BYTES, log::Entry};
+
+use std::io::{self, Read, Write};
+
+const TAG_VOTE: u8 = 0;
+const TAG_VOTE_REPLY: u8 = 1;
+const TAG_APPEND: u8 = 2;
+const TAG_APPEND_REPLY: u8 = 3;
+
+/// Largest frame a node accepts: an append request's fields and a full
+/// batch of entries. Longer length prefixes are corrupt and rejected before
+/// anything is allocated.
+const MAX_FRAME: usize = 1 + 6 * 8 + MAX_BATCH_BYTES as usize;
+
+#[derive(Debug)]
+pub(super) enum Message {
+    Vote {
+        term: u64,
+        candidate: usize,
+        last_len: u64,
+        last_term: u64,
+    },
+    VoteReply {
+        term: u64,
+        granted: bool,
+    },
+    Append {
+        term: u64,
+        leader: usize,
+        prev_len: u64,
+        prev_term: u64,
+        commit: u64,
+        entries: Vec<Entry>,
+    },
+    AppendReply {
+        term: u64,
+        success: bool,
+        /// On success, the length of the log known to match the leader's.
+        /// On failure, a hint for where the leader should retry from.
+        len: u64,
+    },
+}
+
+impl Message {
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut buf = Vec::new();
+        match self {
+            Message::Vote {
+                term,
+                candidate,
+                last_len,
+                last_term,
+            } => {
+                buf.push(TAG_VOTE);
+                put(&mut buf, &[*term, *candidate as u64, *last_len, *last_term]);
+            }
+            Message::VoteReply { term, granted } => {
+                buf.push(TAG_VOTE_REPLY);
+                put(&mut buf, &[*term, *granted as u64]);
+            }
+            Message::Append {
+                term,
+                leader,
+                prev_len,
+                prev_term,
+                commit,
+                entries,
+            } => {
+                buf.push(TAG_APPEND);
+                put(
+                    &mut buf,
+                    &[
+                        *term,
+                        *leader as u64,
+                        *prev_len,
+                        *prev_term,
+                        *commit,
+                        entries.len() as u64,
+                    ],
+                );
+                for entry in entries {
+                    entry.write_to(&mut buf)?;
+                }
+            }
+            Message::AppendReply { term, success, len } => {
+                buf.push(TAG_APPEND_REPLY);
+                put(&mut buf, &[*term, *success as u64, *len]);
+            }
+        }
+
+        w.write_all(&(buf.len() as u32).to_le_bytes())?;
+        w.write_all(&buf)?;
+        w.flush()
+    }
+
+    pub fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let mut len = [0; 4];
+        r.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len == 0 || len > MAX_FRAME {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let mut buf = vec![0; len];
+        r.read_exact(&mut buf)?;
+        let mut r = &buf[1..];
+
+        let message = match buf[0] {
+            TAG_VOTE => Message::Vote {
+                term: get(&mut r)?,
+                candidate: get(&mut r)? as usize,
+                last_len: get(&mut r)?,
+                last_term: get(&mut r)?,
+            },
+            TAG_VOTE_REPLY => Message::VoteReply {
+                term: get(&mut r)?,
+                granted: get(&mut r)? != 0,
+            },
+            TAG_APPEND => {
+                let term = get(&mut r)?;
+                let leader = get(&mut r)? as usize;
+                let prev_len = get(&mut r)?;
+                let prev_term = get(&mut r)?;
+                let commit = get(&mut r)?;
+                let count = get(&mut r)?;
+                if count > MAX_BATCH as u64 {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                let entries = (0..count)
+                    .map(|_| Entry::read_from(&mut r))
+                    .collect::<io::Result<_>>()?;
+
+                Message::Append {
+                    term,
+                    leader,
+                    prev_len,
+                    prev_term,
+                    commit,
+                    entries,
+                }
+            }
+            TAG_APPEND_REPLY => Message::AppendReply {
+                term: get(&mut r)?,
+                success: get(&mut r)? != 0,
+                len: get(&mut r)?,
+            },
+            _ => return Err(io::ErrorKind::InvalidData.into()),
+        };
+
+        Ok(message)
+    }
+}
+
+fn put(buf: &mut Vec<u8>, values: &[u64]) {
+    for value in values {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn get(r: &mut &[u8]) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_frame_is_rejected() {
+        let frame = (MAX_FRAME as u32 + 1).to_le_bytes();
+        let err = Message::read_from(&mut &frame[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn oversized_batch_is_rejected() {
+        let mut buf = vec![TAG_APPEND];
+        put(&mut buf, &[1, 0, 0, 0, 0, MAX_BATCH as u64 + 1]);
+        let mut frame = (buf.len() as u32).to_le_bytes().to_vec();
+        frame.extend_from_slice(&buf);
+
+        let err = Message::read_from(&mut &frame[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn append_round_trips() {
+        let message = Message::Append {
+            term: 3,
+            leader: 1,
+            prev_len: 2,
+            prev_term: 2,
+            commit: 2,
+            entries: vec![Entry {
+                term: 3,
+                data: Some(b"event".to_vec()),
+            }],
+        };
+        let mut frame = Vec::new();
+        message.write_to(&mut frame).unwrap();
+
+        let Message::Append { term, entries, .. } = Message::read_from(&mut &frame[..]).unwrap()
+        else {
+            panic!("decoded a different message");
+        };
+        assert_eq!(term, 3);
+        assert_eq!(entries[0].data.as_deref(), Some(&b"event"[..]));
+    }
+}
@@ -0,0 +1,782 @@
+//! Embedded Raft-replicated log.
+//!
+//! Enabled with the `raft` feature. A group of three or five [`RaftNode`]s
+//! replicate the event log among themselves over TCP, removing the need for
+//! an external broker and a separate lock service. Each node implements
+//! [`Stream`], [`Producer`] and [`Election`], so the same node is passed to
+//! [`SequencerBuilder::run`](crate::sequencer::SequencerBuilder::run) for all
+//! three roles:
+//!
+//! - [`Producer::publish`] returns once the event is committed by a quorum.
+//! - [`Stream::subscribe`] delivers committed entries only.
+//! - [`Election`] is won by the Raft leader, once it has committed an entry
+//!   in its own term, and reports the leader's address in the group as the
+//!   one at which it accepts commands.
+//!
+//! The full log is kept in memory and persisted to a file in the node's data
+//! directory, alongside its voting state.
+
+mod log;
+mod message;
+
+use crate::{
+    Receiver,
+    election::{Election, Leader},
+    stream::{Producer, Stream},
+};
+
+use log::{Entry, Log, MAX_DATA, Meta};
+use message::Message;
+
+use std::{
+    io::{self, BufReader, BufWriter},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Maximum number of entries sent in a single append request.
+const MAX_BATCH: usize = 1024;
+
+/// Maximum size of the entries sent in a single append request, which
+/// holds at least one entry of up to [`MAX_DATA`] bytes.
+const MAX_BATCH_BYTES: u64 = 8 << 20;
+
+/// Configuration for a [`RaftNode`].
+pub struct RaftConfig {
+    /// This node's index into `peers`.
+    pub id: usize,
+    /// Addresses of every node in the group, including this one.
+    pub peers: Vec<SocketAddr>,
+    /// Directory holding this node's log and voting state.
+    pub dir: PathBuf,
+    /// Minimum time without hearing from a leader before starting an
+    /// election. Actual timeouts are randomized up to twice this value.
+    ///
+    /// A leader that has not heard from a quorum within this window steps
+    /// down, and followers that have heard from a leader within it refuse to
+    /// vote for other candidates.
+    pub election_timeout: Duration,
+    /// Interval at which the leader sends append requests when idle.
+    pub heartbeat_interval: Duration,
+}
+
+impl RaftConfig {
+    /// Creates a configuration with a 300ms election timeout and a 50ms
+    /// heartbeat interval.
+    pub fn new(id: usize, peers: Vec<SocketAddr>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            id,
+            peers,
+            dir: dir.into(),
+            election_timeout: Duration::from_millis(300),
+            heartbeat_interval: Duration::from_millis(50),
+        }
+    }
+
+    fn quorum(&self) -> usize {
+        self.peers.len() / 2 + 1
+    }
+
+    /// How long after the start of a successful renewal no other node can
+    /// be elected: a quorum acknowledged the leader within this window, and
+    /// each of them refuses votes for an election timeout after hearing
+    /// from it.
+    fn lease(&self) -> Duration {
+        self.election_timeout / 2
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+struct State {
+    meta: Meta,
+    log: Log,
+    /// Number of committed entries.
+    commit: u64,
+    role: Role,
+    /// When a follower or candidate next starts an election.
+    deadline: Instant,
+    /// When this node last heard from a current leader.
+    heard: Option<Instant>,
+    /// The leader of the current term, once known.
+    leader: Option<usize>,
+    /// Candidate: peers a vote request was sent to in this term.
+    requested: Vec<bool>,
+    /// Candidate: peers that granted their vote in this term.
+    granted: Vec<bool>,
+    /// Leader: index of the next entry to send to each peer.
+    next: Vec<u64>,
+    /// Leader: length of the log known to be replicated on each peer.
+    matched: Vec<u64>,
+    /// Leader: send time of the latest request each peer acknowledged.
+    acked: Vec<Option<Instant>>,
+    /// Leader: when an append request was last sent to each peer.
+    sent: Vec<Option<Instant>>,
+    /// Leader: offset of the no-op entry that opened this term.
+    start: u64,
+    /// Leader: when this node won its election.
+    elected: Instant,
+    rng: u64,
+}
+
+struct Shared {
+    config: RaftConfig,
+    state: Mutex<State>,
+    /// Signaled when the commit index, term or role changes.
+    changed: Condvar,
+    /// Signaled when replication threads have work to do.
+    wake: Condvar,
+}
+
+/// A member of a Raft group.
+///
+/// Cloning a node yields another handle to the same member.
+#[derive(Clone)]
+pub struct RaftNode {
+    shared: Arc<Shared>,
+}
+
+impl RaftNode {
+    /// Recovers persisted state from the data directory, binds this node's
+    /// address and starts its background threads.
+    pub fn start(config: RaftConfig) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        let meta = Meta::open(config.dir.join("meta"))?;
+        let log = Log::open(&config.dir.join("log"))?;
+        let listener = TcpListener::bind(config.peers[config.id])?;
+
+        let n = config.peers.len();
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+
+        let mut state = State {
+            meta,
+            log,
+            commit: 0,
+            role: Role::Follower,
+            deadline: Instant::now(),
+            heard: None,
+            leader: None,
+            requested: vec![false; n],
+            granted: vec![false; n],
+            next: vec![0; n],
+            matched: vec![0; n],
+            acked: vec![None; n],
+            sent: vec![None; n],
+            start: 0,
+            elected: Instant::now(),
+            rng: (seed ^ (config.id as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)) | 1,
+        };
+        state.reset_deadline(config.election_timeout);
+
+        let shared = Arc::new(Shared {
+            config,
+            state: Mutex::new(state),
+            changed: Condvar::new(),
+            wake: Condvar::new(),
+        });
+
+        let s = Arc::clone(&shared);
+        thread::spawn(move || s.accept(listener));
+
+        let s = Arc::clone(&shared);
+        thread::spawn(move || s.tick());
+
+        for peer in (0..n).filter(|&peer| peer != shared.config.id) {
+            let s = Arc::clone(&shared);
+            thread::spawn(move || s.replicate(peer));
+        }
+
+        Ok(Self { shared })
+    }
+
+    /// Returns `true` if this node currently believes it is the leader.
+    pub fn is_leader(&self) -> bool {
+        self.shared.lock().role == Role::Leader
+    }
+
+    /// Returns the current term.
+    pub fn term(&self) -> u64 {
+        self.shared.lock().meta.term
+    }
+
+    /// Returns the number of committed entries.
+    pub fn committed(&self) -> u64 {
+        self.shared.lock().commit
+    }
+}
+
+impl Stream for RaftNode {
+    type Receiver = RaftReceiver;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        RaftReceiver {
+            shared: Arc::clone(&self.shared),
+            next: Mutex::new(offset),
+        }
+    }
+}
+
+/// Receiver over a node's committed log.
+pub struct RaftReceiver {
+    shared: Arc<Shared>,
+    next: Mutex<u64>,
+}
+
+impl Receiver for RaftReceiver {
+    fn recv(&self) -> Vec<u8> {
+        let mut next = self.next.lock().unwrap();
+        let mut state = self.shared.lock();
+
+        loop {
+            while state.commit <= *next {
+                state = self.shared.changed.wait(state).unwrap();
+            }
+
+            let entry = state.log.get(*next);
+            *next += 1;
+            if let Some(data) = &entry.data {
+                return data.clone();
+            }
+        }
+    }
+}
+
+impl Producer for RaftNode {
+    /// Appends the event and blocks until a quorum has committed it.
+    ///
+    /// Panics if the event is larger than 1 MiB, if this node is not the
+    /// leader, or if it loses leadership before the entry is known to be
+    /// committed.
+    fn publish(&self, data: &[u8]) {
+        assert!(data.len() <= MAX_DATA, "event exceeds the raft entry limit");
+        let shared = &self.shared;
+        let mut state = shared.lock();
+        assert!(state.role == Role::Leader, "raft node is not the leader");
+
+        let term = state.meta.term;
+        let index = state.log.len();
+        let entry = Entry {
+            term,
+            data: Some(data.to_vec()),
+        };
+        state.log.append(&[entry]).expect("raft log write failed");
+        shared.advance_commit(&mut state);
+        shared.wake.notify_all();
+
+        while state.commit <= index {
+            assert!(
+                state.meta.term == term && state.role == Role::Leader,
+                "raft node lost leadership before the event was committed"
+            );
+            state = shared.changed.wait(state).unwrap();
+        }
+    }
+}
+
+impl Election for RaftNode {
+    /// Returns `true` if this node is the leader and has committed the no-op
+    /// entry opening its term, so its log holds every committed event.
+    fn elect(&self) -> bool {
+        let state = self.shared.lock();
+        state.role == Role::Leader && state.commit > state.start
+    }
+
+    /// Returns `true` if this node is still the leader and a quorum has
+    /// acknowledged it within half the election timeout.
+    fn renew(&self) -> bool {
+        let state = self.shared.lock();
+        state.role == Role::Leader
+            && self
+                .shared
+                .has_quorum(&state, Instant::now(), self.shared.config.lease())
+    }
+
+    /// Returns half the election timeout, since followers refuse to vote
+    /// for an election timeout after hearing from the leader, and a renewal
+    /// succeeds only if a quorum heard from it within half of one.
+    fn lease(&self) -> Option<Duration> {
+        Some(self.shared.config.lease())
+    }
+
+    /// Steps down and stops sending heartbeats. Followers elect another
+    /// node once an election timeout passes without hearing from a leader,
+    /// while this node waits an extra election timeout before standing.
+    fn resign(&self) {
+        let mut state = self.shared.lock();
+        if state.role != Role::Leader {
+            return;
+        }
+
+        let timeout = self.shared.config.election_timeout;
+        state.role = Role::Follower;
+        state.leader = None;
+        state.reset_deadline(timeout);
+        state.deadline += timeout;
+        self.shared.changed.notify_all();
+    }
+
+    /// Returns this node while it leads, or the leader a follower heard
+    /// from within the election timeout, with its address in the group.
+    fn leader(&self) -> Option<Leader> {
+        let state = self.shared.lock();
+        let config = &self.shared.config;
+        let leader = match state.role {
+            Role::Leader => config.id,
+            Role::Follower
+                if state
+                    .heard
+                    .is_some_and(|at| at.elapsed() < config.election_timeout) =>
+            {
+                state.leader?
+            }
+            _ => return None,
+        };
+
+        Some(Leader {
+            id: leader.to_string(),
+            address: Some(config.peers[leader].to_string()),
+        })
+    }
+}
+
+impl State {
+    fn reset_deadline(&mut self, timeout: Duration) {
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+
+        let jitter = self.rng % (timeout.as_nanos() as u64).max(1);
+        self.deadline = Instant::now() + timeout + Duration::from_nanos(jitter);
+    }
+
+    fn persist(&self) {
+        self.meta.store().expect("raft state write failed");
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Adopts a newer term seen in any message and reverts to follower.
+    fn observe_term(&self, state: &mut State, term: u64) {
+        if term <= state.meta.term {
+            return;
+        }
+
+        state.meta.term = term;
+        state.meta.voted_for = None;
+        state.leader = None;
+        state.persist();
+        if state.role != Role::Follower {
+            state.role = Role::Follower;
+            state.reset_deadline(self.config.election_timeout);
+        }
+        self.changed.notify_all();
+    }
+
+    /// Returns `true` if a quorum acknowledged requests sent within
+    /// `window` of `now`.
+    fn has_quorum(&self, state: &State, now: Instant, window: Duration) -> bool {
+        let acked = (0..self.config.peers.len())
+            .filter(|&peer| {
+                peer == self.config.id
+                    || state.acked[peer].is_some_and(|at| now.duration_since(at) < window)
+            })
+            .count();
+
+        acked >= self.config.quorum()
+    }
+
+    fn start_election(&self, state: &mut State) {
+        let id = self.config.id;
+        state.meta.term += 1;
+        state.meta.voted_for = Some(id);
+        state.leader = None;
+        state.persist();
+
+        state.role = Role::Candidate;
+        state.requested.fill(false);
+        state.granted.fill(false);
+        state.granted[id] = true;
+        state.reset_deadline(self.config.election_timeout);
+
+        self.check_votes(state);
+        self.changed.notify_all();
+        self.wake.notify_all();
+    }
+
+    fn check_votes(&self, state: &mut State) {
+        let votes = state.granted.iter().filter(|&&granted| granted).count();
+        if state.role == Role::Candidate && votes >= self.config.quorum() {
+            self.become_leader(state);
+        }
+    }
+
+    fn become_leader(&self, state: &mut State) {
+        let now = Instant::now();
+        let len = state.log.len();
+
+        state.role = Role::Leader;
+        state.leader = Some(self.config.id);
+        state.elected = now;
+        state.next.fill(len);
+        state.matched.fill(0);
+        state.sent.fill(None);
+        state.acked.fill(None);
+
+        // Commit entries from earlier terms by committing one from this term.
+        state.start = len;
+        let noop = Entry {
+            term: state.meta.term,
+            data: None,
+        };
+        state.log.append(&[noop]).expect("raft log write failed");
+        self.advance_commit(state);
+
+        self.changed.notify_all();
+        self.wake.notify_all();
+    }
+
+    /// Advances the commit index to the highest entry from the current term
+    /// replicated on a quorum.
+    fn advance_commit(&self, state: &mut State) {
+        let mut matched = state.matched.clone();
+        matched[self.config.id] = state.log.len();
+        matched.sort_unstable_by(|a, b| b.cmp(a));
+
+        let len = matched[self.config.quorum() - 1];
+        if len > state.commit && state.log.term_before(len) == state.meta.term {
+            state.commit = len;
+            self.changed.notify_all();
+        }
+    }
+
+    fn handle(&self, message: Message) -> Message {
+        let mut state = self.lock();
+        let now = Instant::now();
+
+        match message {
+            Message::Vote {
+                term,
+                candidate,
+                last_len,
+                last_term,
+            } => {
+                // Ignore candidates while a leader is known to be alive, so a
+                // rejoining node cannot depose a leader whose lease is valid.
+                let leader_alive = state.role == Role::Leader
+                    || state
+                        .heard
+                        .is_some_and(|at| now.duration_since(at) < self.config.election_timeout);
+                if term > state.meta.term && leader_alive {
+                    return Message::VoteReply {
+                        term: state.meta.term,
+                        granted: false,
+                    };
+                }
+
+                self.observe_term(&mut state, term);
+                let up_to_date = (last_term, last_len) >= (state.log.last_term(), state.log.len());
+                let granted = term == state.meta.term
+                    && up_to_date
+                    && state.meta.voted_for.is_none_or(|id| id == candidate);
+
+                if granted {
+                    state.meta.voted_for = Some(candidate);
+                    state.persist();
+                    state.reset_deadline(self.config.election_timeout);
+                }
+
+                Message::VoteReply {
+                    term: state.meta.term,
+                    granted,
+                }
+            }
+
+            Message::Append {
+                term,
+                leader,
+                prev_len,
+                prev_term,
+                commit,
+                entries,
+            } => {
+                if term < state.meta.term {
+                    return Message::AppendReply {
+                        term: state.meta.term,
+                        success: false,
+                        len: 0,
+                    };
+                }
+
+                self.observe_term(&mut state, term);
+                if state.role == Role::Candidate {
+                    state.role = Role::Follower;
+                    self.changed.notify_all();
+                }
+                state.heard = Some(now);
+                if leader < self.config.peers.len() {
+                    state.leader = Some(leader);
+                }
+                state.reset_deadline(self.config.election_timeout);
+
+                if prev_len > state.log.len() {
+                    return Message::AppendReply {
+                        term,
+                        success: false,
+                        len: state.log.len(),
+                    };
+                }
+                if state.log.term_before(prev_len) != prev_term {
+                    return Message::AppendReply {
+                        term,
+                        success: false,
+                        len: prev_len - 1,
+                    };
+                }
+
+                // Skip entries already present; truncate at the first
+                // conflict and append the remainder.
+                let mut index = prev_len;
+                let mut entries = entries.as_slice();
+                while let Some((entry, rest)) = entries.split_first() {
+                    if index >= state.log.len() {
+                        break;
+                    }
+                    if state.log.get(index).term != entry.term {
+                        state.log.truncate(index).expect("raft log write failed");
+                        break;
+                    }
+                    index += 1;
+                    entries = rest;
+                }
+                state.log.append(entries).expect("raft log write failed");
+
+                let len = index + entries.len() as u64;
+                let commit = commit.min(len);
+                if commit > state.commit {
+                    state.commit = commit;
+                    self.changed.notify_all();
+                }
+
+                Message::AppendReply {
+                    term,
+                    success: true,
+                    len,
+                }
+            }
+
+            Message::VoteReply { term, .. } | Message::AppendReply { term, .. } => {
+                Message::AppendReply {
+                    term: state.meta.term.max(term),
+                    success: false,
+                    len: 0,
+                }
+            }
+        }
+    }
+
+    fn handle_reply(
+        &self,
+        state: &mut State,
+        peer: usize,
+        term: u64,
+        sent: Instant,
+        reply: Message,
+    ) {
+        match reply {
+            Message::VoteReply {
+                term: reply_term,
+                granted,
+            } => {
+                self.observe_term(state, reply_term);
+                if state.meta.term == term && granted {
+                    state.granted[peer] = true;
+                    self.check_votes(state);
+                }
+            }
+
+            Message::AppendReply {
+                term: reply_term,
+                success,
+                len,
+            } => {
+                self.observe_term(state, reply_term);
+                if state.meta.term != term || state.role != Role::Leader {
+                    return;
+                }
+
+                // Measured from when the request was sent, which precedes the
+                // moment the peer started refusing votes to other candidates.
+                state.acked[peer] = Some(sent);
+                if success {
+                    state.matched[peer] = state.matched[peer].max(len);
+                    state.next[peer] = len;
+                    self.advance_commit(state);
+                } else {
+                    state.next[peer] = len.max(state.matched[peer]);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Builds the next request for `peer`, if one is due.
+    fn request(&self, state: &mut State, peer: usize) -> Option<Message> {
+        let now = Instant::now();
+
+        match state.role {
+            Role::Candidate if !state.requested[peer] => {
+                state.requested[peer] = true;
+                Some(Message::Vote {
+                    term: state.meta.term,
+                    candidate: self.config.id,
+                    last_len: state.log.len(),
+                    last_term: state.log.last_term(),
+                })
+            }
+
+            Role::Leader => {
+                let next = state.next[peer];
+                let idle = state.sent[peer]
+                    .is_some_and(|at| now.duration_since(at) < self.config.heartbeat_interval);
+                if next >= state.log.len() && idle {
+                    return None;
+                }
+
+                state.sent[peer] = Some(now);
+                Some(Message::Append {
+                    term: state.meta.term,
+                    leader: self.config.id,
+                    prev_len: next,
+                    prev_term: state.log.term_before(next),
+                    commit: state.commit,
+                    entries: state.log.slice(next, MAX_BATCH, MAX_BATCH_BYTES),
+                })
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Sends requests to `peer` for as long as the node runs.
+    fn replicate(&self, peer: usize) {
+        let addr = self.config.peers[peer];
+        let mut conn = None;
+        let mut state = self.lock();
+
+        loop {
+            let Some(request) = self.request(&mut state, peer) else {
+                state = self
+                    .wake
+                    .wait_timeout(state, self.config.heartbeat_interval / 2)
+                    .unwrap()
+                    .0;
+                continue;
+            };
+
+            let term = state.meta.term;
+            drop(state);
+            let sent = Instant::now();
+            let reply = self.call(&mut conn, addr, &request);
+            state = self.lock();
+
+            match reply {
+                Ok(reply) => self.handle_reply(&mut state, peer, term, sent, reply),
+                Err(_) => {
+                    conn = None;
+                    if let Message::Vote { .. } = request
+                        && state.meta.term == term
+                    {
+                        state.requested[peer] = false;
+                    }
+                    state = self
+                        .wake
+                        .wait_timeout(state, self.config.heartbeat_interval)
+                        .unwrap()
+                        .0;
+                }
+            }
+        }
+    }
+
+    fn call(
+        &self,
+        conn: &mut Option<(BufReader<TcpStream>, BufWriter<TcpStream>)>,
+        addr: SocketAddr,
+        request: &Message,
+    ) -> io::Result<Message> {
+        if conn.is_none() {
+            let stream = TcpStream::connect_timeout(&addr, self.config.election_timeout)?;
+            stream.set_nodelay(true)?;
+            stream.set_read_timeout(Some(self.config.election_timeout))?;
+            *conn = Some((BufReader::new(stream.try_clone()?), BufWriter::new(stream)));
+        }
+
+        let (reader, writer) = conn.as_mut().unwrap();
+        request.write_to(writer)?;
+        Message::read_from(reader)
+    }
+
+    fn accept(self: Arc<Self>, listener: TcpListener) {
+        for stream in listener.incoming().flatten() {
+            let shared = Arc::clone(&self);
+            thread::spawn(move || shared.serve(stream));
+        }
+    }
+
+    fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        loop {
+            let request = Message::read_from(&mut reader)?;
+            self.handle(request).write_to(&mut writer)?;
+        }
+    }
+
+    /// Starts elections when the leader goes quiet, and steps down as leader
+    /// when a quorum goes quiet.
+    fn tick(&self) {
+        loop {
+            let mut state = self.lock();
+            let now = Instant::now();
+
+            match state.role {
+                Role::Leader => {
+                    let settled = now.duration_since(state.elected) >= self.config.election_timeout;
+                    if settled && !self.has_quorum(&state, now, self.config.election_timeout) {
+                        state.role = Role::Follower;
+                        state.leader = None;
+                        state.reset_deadline(self.config.election_timeout);
+                        self.changed.notify_all();
+                    }
+                }
+                Role::Follower | Role::Candidate => {
+                    if now >= state.deadline {
+                        self.start_election(&mut state);
+                    }
+                }
+            }
+
+            drop(state);
+            thread::sleep(self.config.heartbeat_interval / 2);
+        }
+    }
+}
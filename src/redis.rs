@@ -0,0 +1,331 @@
+//! Redis-backed implementations of [`Election`], [`Stream`] and [`Producer`].
+//!
+//! Enabled with the `redis` feature. The stream uses Redis Streams and
+//! requires Redis 7.0 or later.
+
+use crate::{
+    Receiver,
+    election::{Election, Leader},
+    stream::{Producer, Stream},
+};
+
+use ::redis::{Client, Connection, RedisResult, Script, streams::StreamReadReply};
+
+use std::{
+    collections::VecDeque,
+    process,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// Delay between attempts when the server is unreachable.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a single `XREAD` blocks on the server before polling again.
+const BLOCK_MILLIS: u64 = 1000;
+
+/// Maximum number of entries fetched by a single `XREAD`.
+const READ_COUNT: usize = 1024;
+
+/// Field under which event payloads are stored in each stream entry.
+const FIELD: &str = "data";
+
+/// Extends the lease only if this candidate still holds it.
+const RENEW: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+";
+
+/// Deletes the key only if this candidate still holds it.
+const RESIGN: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+/// A lazily (re)established connection to a Redis server.
+///
+/// Any command error drops the connection so the next call reconnects.
+struct Conn {
+    client: Client,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl Conn {
+    fn open(url: &str) -> RedisResult<Self> {
+        Ok(Self {
+            client: Client::open(url)?,
+            connection: Mutex::new(None),
+        })
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Connection) -> RedisResult<T>) -> RedisResult<T> {
+        let mut guard = self.connection.lock().unwrap();
+        let connection = match guard.as_mut() {
+            Some(connection) => connection,
+            None => guard.insert(self.client.get_connection()?),
+        };
+
+        let result = f(connection);
+        if result.is_err() {
+            *guard = None;
+        }
+        result
+    }
+
+    /// Runs `f` until it succeeds, reconnecting between attempts.
+    fn retry<T>(&self, mut f: impl FnMut(&mut Connection) -> RedisResult<T>) -> T {
+        loop {
+            match self.with(&mut f) {
+                Ok(value) => return value,
+                Err(_) => thread::sleep(RETRY_DELAY),
+            }
+        }
+    }
+}
+
+/// Lease-based [`Election`] using a single Redis key.
+///
+/// Leadership is acquired with `SET key id NX PX lease` and renewed by a
+/// script that extends the expiry only while the key still holds this
+/// candidate's identity. If the leader stops renewing, the key expires and a
+/// standby acquires it on its next [`elect`](Election::elect). The key holds
+/// the candidate's identity followed by the address it
+/// [advertises](RedisElection::advertise), if any.
+///
+/// Errors are reported as a failed election or renewal, since leadership
+/// cannot be confirmed without reaching the server.
+pub struct RedisElection {
+    conn: Conn,
+    key: String,
+    id: String,
+    /// Value stored in the key: the identity and any advertised address.
+    value: String,
+    lease: Duration,
+    renew: Script,
+    resign: Script,
+}
+
+impl RedisElection {
+    /// Creates a candidate competing for `key` on the server at `url`.
+    ///
+    /// Each instance generates a unique holder identity from the process ID
+    /// and creation time.
+    pub fn new(url: &str, key: &str, lease: Duration) -> RedisResult<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+
+        let id = format!("{}-{}", process::id(), nanos);
+        Ok(Self {
+            conn: Conn::open(url)?,
+            key: key.to_owned(),
+            value: id.clone(),
+            id,
+            lease,
+            renew: Script::new(RENEW),
+            resign: Script::new(RESIGN),
+        })
+    }
+
+    /// Sets the address at which this candidate accepts commands, stored
+    /// with its identity and reported by [`leader`](Election::leader).
+    pub fn advertise(mut self, address: &str) -> Self {
+        self.value = format!("{} {}", self.id, address);
+        self
+    }
+
+    /// Returns the identity stored in the key by this candidate.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn lease_millis(&self) -> u64 {
+        self.lease.as_millis() as u64
+    }
+}
+
+impl Election for RedisElection {
+    fn elect(&self) -> bool {
+        let acquired = self.conn.with(|con| {
+            ::redis::cmd("SET")
+                .arg(&self.key)
+                .arg(&self.value)
+                .arg("NX")
+                .arg("PX")
+                .arg(self.lease_millis())
+                .query::<Option<String>>(con)
+        });
+
+        match acquired {
+            Ok(Some(_)) => true,
+            // Already held, possibly by this candidate from an earlier term.
+            Ok(None) => self.renew(),
+            Err(_) => false,
+        }
+    }
+
+    fn renew(&self) -> bool {
+        self.conn
+            .with(|con| {
+                self.renew
+                    .key(&self.key)
+                    .arg(&self.value)
+                    .arg(self.lease_millis())
+                    .invoke::<i64>(con)
+            })
+            .is_ok_and(|renewed| renewed == 1)
+    }
+
+    fn lease(&self) -> Option<Duration> {
+        Some(self.lease)
+    }
+
+    fn resign(&self) {
+        let _ = self.conn.with(|con| {
+            self.resign
+                .key(&self.key)
+                .arg(&self.value)
+                .invoke::<i64>(con)
+        });
+    }
+
+    fn leader(&self) -> Option<Leader> {
+        let value = self
+            .conn
+            .with(|con| {
+                ::redis::cmd("GET")
+                    .arg(&self.key)
+                    .query::<Option<String>>(con)
+            })
+            .ok()??;
+
+        Some(match value.split_once(' ') {
+            Some((id, address)) => Leader {
+                id: id.to_owned(),
+                address: Some(address.to_owned()),
+            },
+            None => Leader {
+                id: value,
+                address: None,
+            },
+        })
+    }
+}
+
+/// [`Stream`] backed by a Redis Stream key.
+///
+/// Offsets map directly onto entry IDs: the event at offset `n` is stored
+/// with ID `0-(n + 1)`. [`RedisProducer`] appends with `XADD key 0-*`, which
+/// has the server assign the next sequence, keeping offsets dense.
+pub struct RedisStream {
+    url: String,
+    key: String,
+}
+
+impl RedisStream {
+    /// Creates a stream over `key` on the server at `url`.
+    pub fn new(url: &str, key: &str) -> RedisResult<Self> {
+        Client::open(url)?;
+
+        Ok(Self {
+            url: url.to_owned(),
+            key: key.to_owned(),
+        })
+    }
+
+    /// Creates a producer that appends to this stream.
+    pub fn producer(&self) -> RedisProducer {
+        RedisProducer {
+            conn: Conn::open(&self.url).unwrap(),
+            key: self.key.clone(),
+        }
+    }
+}
+
+impl Stream for RedisStream {
+    type Receiver = RedisReceiver;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        RedisReceiver {
+            conn: Conn::open(&self.url).unwrap(),
+            key: self.key.clone(),
+            state: Mutex::new(ReceiverState {
+                last_id: format!("0-{offset}"),
+                buffer: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+struct ReceiverState {
+    last_id: String,
+    buffer: VecDeque<Vec<u8>>,
+}
+
+/// Receiver reading a Redis Stream with blocking `XREAD`.
+///
+/// Each receiver owns a dedicated connection, since a blocked `XREAD`
+/// occupies the connection until it returns.
+pub struct RedisReceiver {
+    conn: Conn,
+    key: String,
+    state: Mutex<ReceiverState>,
+}
+
+impl Receiver for RedisReceiver {
+    fn recv(&self) -> Vec<u8> {
+        let mut state = self.state.lock().unwrap();
+
+        while state.buffer.is_empty() {
+            let reply = self.conn.retry(|con| {
+                ::redis::cmd("XREAD")
+                    .arg("COUNT")
+                    .arg(READ_COUNT)
+                    .arg("BLOCK")
+                    .arg(BLOCK_MILLIS)
+                    .arg("STREAMS")
+                    .arg(&self.key)
+                    .arg(&state.last_id)
+                    .query::<Option<StreamReadReply>>(con)
+            });
+
+            for entry in reply.into_iter().flat_map(|r| r.keys).flat_map(|k| k.ids) {
+                let data = entry.get::<Vec<u8>>(FIELD).unwrap_or_default();
+                state.buffer.push_back(data);
+                state.last_id = entry.id;
+            }
+        }
+
+        state.buffer.pop_front().unwrap()
+    }
+}
+
+/// [`Producer`] appending to a Redis Stream with `XADD`.
+///
+/// Redis acknowledges an `XADD` once the entry is applied in memory; durability
+/// beyond that depends on the server's persistence settings (e.g.
+/// `appendfsync always`). A publish interrupted by a connection failure is
+/// retried and may therefore be appended twice.
+pub struct RedisProducer {
+    conn: Conn,
+    key: String,
+}
+
+impl Producer for RedisProducer {
+    fn publish(&self, data: &[u8]) {
+        self.conn.retry(|con| {
+            ::redis::cmd("XADD")
+                .arg(&self.key)
+                .arg("0-*")
+                .arg(FIELD)
+                .arg(data)
+                .query::<String>(con)
+        });
+    }
+}
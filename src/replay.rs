@@ -0,0 +1,372 @@
+//! Offline determinism checks over a recorded stream.
+//!
+//! Enabled with the `replay` feature. A [`Replay`] steps a recorded stream through fresh [`Logic`] instances and
+//! compares their [`state_hash`](Logic::state_hash) after every event: once
+//! against a second full replay, and once against an instance restored from
+//! a snapshot taken mid-stream. Any difference means [`step`](Logic::step)
+//! depends on something other than the events themselves.
+//!
+//! Since the logic under test lives in the application, the checker is
+//! packaged as a library entry point. A small harness crate links the logic
+//! and hands it to [`Replay::main`]:
+//!
+//! ```ignore
+//! fn main() {
+//!     Replay::new(Orderbook::new)
+//!         .restore(|book| Orderbook::from_snapshot(&book.snapshot()))
+//!         .main();
+//! }
+//! ```
+//!
+//! The resulting binary takes a stream file in the [`FileStream`] format.
+//! Streams from other backends can be exported to that format by appending
+//! their events to a [`FileStream`].
+//!
+//! [`FileStream`]: crate::file::FileStream
+
+use crate::{consumer::Divergence, file, logic::Logic};
+
+use std::{env, fmt, process};
+
+/// Creates a restarted instance from a snapshot of another.
+type Restore<L> = Box<dyn Fn(&L) -> L>;
+
+/// Reason a replay check failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The logic does not implement [`state_hash`](Logic::state_hash).
+    Unsupported,
+    /// A fresh instance loaded at an offset past the end of the stream.
+    Offset {
+        /// Offset returned by [`load`](Logic::load).
+        offset: u64,
+        /// Number of events in the stream.
+        len: u64,
+    },
+    /// Two full replays disagreed.
+    Nondeterministic(Divergence),
+    /// An instance restored from a snapshot disagreed with a full replay.
+    Snapshot {
+        /// Offset at which the snapshot was taken.
+        at: u64,
+        /// Where the restored instance first disagreed.
+        divergence: Divergence,
+    },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Unsupported => write!(f, "logic does not implement state_hash"),
+            Failure::Offset { offset, len } => write!(
+                f,
+                "logic loaded at offset {offset}, past the end of the stream at {len}"
+            ),
+            Failure::Nondeterministic(divergence) => {
+                write!(f, "replays disagree: {divergence}")
+            }
+            Failure::Snapshot { at, divergence } => {
+                write!(
+                    f,
+                    "instance restored at offset {at} disagrees: {divergence}"
+                )
+            }
+        }
+    }
+}
+
+/// A determinism check of the logic created by `fresh`.
+pub struct Replay<L, F> {
+    fresh: F,
+    restore: Option<Restore<L>>,
+    snapshot_at: Option<u64>,
+}
+
+impl<L, F> Replay<L, F>
+where
+    L: Logic,
+    F: Fn() -> L,
+{
+    /// Creates a check of instances from `fresh`.
+    pub fn new(fresh: F) -> Self {
+        Self {
+            fresh,
+            restore: None,
+            snapshot_at: None,
+        }
+    }
+
+    /// Enables the snapshot check, using `restore` to create a new instance
+    /// from a snapshot of the given one, as a restarted process would.
+    pub fn restore(mut self, restore: impl Fn(&L) -> L + 'static) -> Self {
+        self.restore = Some(Box::new(restore));
+        self
+    }
+
+    /// Sets the offset at which the snapshot is taken. Defaults to the middle
+    /// of the replayed events.
+    pub fn snapshot_at(mut self, offset: u64) -> Self {
+        self.snapshot_at = Some(offset);
+        self
+    }
+
+    /// Checks `events`, the stream from offset `0`, returning the number of
+    /// events replayed.
+    pub fn check(&self, events: &[Vec<u8>]) -> Result<u64, Failure> {
+        let (start, first) = self.replay(events)?;
+        let (_, second) = self.replay(events)?;
+
+        if let Some(divergence) = compare(start, &first, &second) {
+            return Err(Failure::Nondeterministic(divergence));
+        }
+
+        if let Some(restore) = &self.restore {
+            let len = first.len() as u64;
+            let at = self
+                .snapshot_at
+                .unwrap_or(start + len / 2)
+                .clamp(start, start + len);
+
+            let mut logic = (self.fresh)();
+            logic.load();
+            for event in &events[start as usize..at as usize] {
+                logic.step(event);
+            }
+
+            let mut restored = restore(&logic);
+            let resume = restored.load();
+            if resume > events.len() as u64 {
+                return Err(Failure::Offset {
+                    offset: resume,
+                    len: events.len() as u64,
+                });
+            }
+
+            let hashes = step(&mut restored, &events[resume as usize..]);
+            let expected = &first[(resume.max(start) - start) as usize..];
+            let actual = &hashes[(start.saturating_sub(resume)) as usize..];
+            if let Some(divergence) = compare(resume.max(start), expected, actual) {
+                return Err(Failure::Snapshot { at, divergence });
+            }
+        }
+
+        Ok(first.len() as u64)
+    }
+
+    /// Parses the command line, checks the stream it names, and exits.
+    ///
+    /// Usage: `<binary> <stream-file> [--snapshot-at <offset>]`. Exits with
+    /// status `0` if the logic is deterministic, `1` if a check failed, and
+    /// `2` on usage or I/O errors.
+    pub fn main(self) -> ! {
+        let mut args = env::args().skip(1);
+        let mut path = None;
+        let mut replay = self;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--snapshot-at" => match args.next().and_then(|offset| offset.parse().ok()) {
+                    Some(offset) => replay = replay.snapshot_at(offset),
+                    None => usage(),
+                },
+                _ if path.is_none() => path = Some(arg),
+                _ => usage(),
+            }
+        }
+        let Some(path) = path else { usage() };
+
+        let events = match file::read_events(&path) {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("failed to read {path}: {e}");
+                process::exit(2);
+            }
+        };
+
+        match replay.check(&events) {
+            Ok(count) => {
+                println!("replayed {count} events: deterministic");
+                process::exit(0);
+            }
+            Err(failure) => {
+                println!("{failure}");
+                process::exit(1);
+            }
+        }
+    }
+
+    /// Replays `events` into a fresh instance, returning the offset it loaded
+    /// at and its state hash after each event from there.
+    fn replay(&self, events: &[Vec<u8>]) -> Result<(u64, Vec<u64>), Failure> {
+        let mut logic = (self.fresh)();
+        let start = logic.load();
+        if start > events.len() as u64 {
+            return Err(Failure::Offset {
+                offset: start,
+                len: events.len() as u64,
+            });
+        }
+        if logic.state_hash().is_none() {
+            return Err(Failure::Unsupported);
+        }
+
+        Ok((start, step(&mut logic, &events[start as usize..])))
+    }
+}
+
+/// Steps each event, recording the state hash after it.
+fn step<L: Logic>(logic: &mut L, events: &[Vec<u8>]) -> Vec<u64> {
+    events
+        .iter()
+        .map(|event| {
+            logic.step(event);
+            logic.state_hash().unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Returns the first position at which two hash sequences starting at
+/// `start` disagree.
+fn compare(start: u64, expected: &[u64], actual: &[u64]) -> Option<Divergence> {
+    expected
+        .iter()
+        .zip(actual)
+        .position(|(expected, actual)| expected != actual)
+        .map(|i| Divergence {
+            offset: start + i as u64,
+            expected: expected[i],
+            actual: actual[i],
+        })
+}
+
+fn usage() -> ! {
+    eprintln!("usage: replay <stream-file> [--snapshot-at <offset>]");
+    process::exit(2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    /// Sums the first byte of each event, resuming from a snapshot of its
+    /// offset and sum. With `skew`, it adds one to its hash from the given
+    /// count of events on.
+    #[derive(Default)]
+    struct Sum {
+        offset: u64,
+        sum: u64,
+        skew: Option<u64>,
+    }
+
+    impl Logic for Sum {
+        fn load(&mut self) -> u64 {
+            self.offset
+        }
+
+        fn step(&mut self, event: &[u8]) -> bool {
+            self.offset += 1;
+            self.sum += u64::from(event[0]);
+            true
+        }
+
+        fn caught_up(&mut self) -> bool {
+            true
+        }
+
+        fn state_hash(&self) -> Option<u64> {
+            let skewed = self.skew.is_some_and(|from| self.offset >= from);
+            Some(self.sum + u64::from(skewed))
+        }
+    }
+
+    fn events() -> Vec<Vec<u8>> {
+        (1..=6).map(|i| vec![i]).collect()
+    }
+
+    #[test]
+    fn deterministic_logic_passes() {
+        let replay = Replay::new(Sum::default).restore(|sum| Sum {
+            offset: sum.offset,
+            sum: sum.sum,
+            skew: None,
+        });
+        assert_eq!(replay.check(&events()), Ok(6));
+    }
+
+    #[test]
+    fn reports_first_disagreeing_offset() {
+        // Every instance after the first skews its hash from the third
+        // event, at offset 2.
+        let created = Cell::new(0);
+        let replay = Replay::new(|| {
+            created.set(created.get() + 1);
+            Sum {
+                skew: (created.get() > 1).then_some(3),
+                ..Sum::default()
+            }
+        });
+
+        assert_eq!(
+            replay.check(&events()),
+            Err(Failure::Nondeterministic(Divergence {
+                offset: 2,
+                expected: 6,
+                actual: 7,
+            }))
+        );
+    }
+
+    #[test]
+    fn checks_resume_from_snapshot() {
+        let forgetful = Replay::new(Sum::default).restore(|sum| Sum {
+            offset: sum.offset,
+            ..Sum::default()
+        });
+        assert_eq!(
+            forgetful.check(&events()),
+            Err(Failure::Snapshot {
+                at: 3,
+                divergence: Divergence {
+                    offset: 3,
+                    expected: 10,
+                    actual: 4,
+                },
+            })
+        );
+
+        let restarted = Replay::new(Sum::default)
+            .restore(|sum| Sum {
+                offset: sum.offset,
+                sum: sum.sum,
+                skew: None,
+            })
+            .snapshot_at(5);
+        assert_eq!(restarted.check(&events()), Ok(6));
+    }
+
+    #[test]
+    fn rejects_logic_without_state_hash() {
+        struct Opaque;
+
+        impl Logic for Opaque {
+            fn load(&mut self) -> u64 {
+                0
+            }
+
+            fn step(&mut self, _event: &[u8]) -> bool {
+                true
+            }
+
+            fn caught_up(&mut self) -> bool {
+                true
+            }
+        }
+
+        assert_eq!(
+            Replay::new(|| Opaque).check(&events()),
+            Err(Failure::Unsupported)
+        );
+    }
+}
@@ -0,0 +1,697 @@
+//! Network stream server with synchronous replication to followers.
+//!
+//! Enabled with the `replication` feature. A primary [`Server`] serves a
+//! [`FileStream`] over TCP: remote processes subscribe through
+//! [`RemoteStream`] and publish through [`RemoteProducer`]. A publish is
+//! acknowledged only once the event is durable on the primary and on a
+//! configured number of follower servers, each of which keeps its own copy of
+//! the log and also serves subscriptions. The primary delivers an event to
+//! subscribers only once it is replicated as well, so consumers never act on
+//! an event that a failover could lose.
+//!
+//! Failover is manual: promote the most up-to-date follower by restarting it
+//! as a primary. Since every follower holds a prefix of that follower's log,
+//! the events followers serve survive the failover.
+//!
+//! The `evcore-stream` binary runs a server from the command line.
+
+use crate::{
+    Receiver,
+    file::FileStream,
+    stream::{Producer, Stream},
+};
+
+use std::{
+    collections::HashMap,
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// Delay between attempts when a server is unreachable.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a streaming connection waits for an event before checking
+/// whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Delay after a failed accept, so persistent failures do not spin.
+const ACCEPT_DELAY: Duration = Duration::from_millis(100);
+
+/// Largest frame accepted, guarding against corrupt length prefixes.
+const MAX_FRAME: usize = 64 << 20;
+
+const TAG_SUBSCRIBE: u8 = 0;
+const TAG_PUBLISH: u8 = 1;
+const TAG_REPLICATE: u8 = 2;
+const TAG_EVENT: u8 = 3;
+const TAG_ACK: u8 = 4;
+const TAG_REJECT: u8 = 5;
+
+/// A protocol message.
+///
+/// Each frame is a little-endian `u32` length, a one-byte tag, and the
+/// payload. A connection's first frame determines its purpose.
+enum Frame {
+    /// Client to server: stream events starting at the given offset.
+    Subscribe(u64),
+    /// Client to server: append an event.
+    Publish(Vec<u8>),
+    /// Follower to primary: stream events starting at the given offset, and
+    /// expect acknowledgments in return.
+    Replicate(u64),
+    /// Server to client or follower: the next event.
+    Event(Vec<u8>),
+    /// Server to client: the offset of a published event. Follower to
+    /// primary: the length of the follower's durable log.
+    Ack(u64),
+    /// Server to client or follower: the request cannot be served.
+    Reject(String),
+}
+
+impl Frame {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let (tag, payload) = match self {
+            Frame::Subscribe(offset) => (TAG_SUBSCRIBE, &offset.to_le_bytes()[..]),
+            Frame::Publish(data) => (TAG_PUBLISH, data.as_slice()),
+            Frame::Replicate(offset) => (TAG_REPLICATE, &offset.to_le_bytes()[..]),
+            Frame::Event(data) => (TAG_EVENT, data.as_slice()),
+            Frame::Ack(offset) => (TAG_ACK, &offset.to_le_bytes()[..]),
+            Frame::Reject(reason) => (TAG_REJECT, reason.as_bytes()),
+        };
+
+        w.write_all(&(payload.len() as u32 + 1).to_le_bytes())?;
+        w.write_all(&[tag])?;
+        w.write_all(payload)?;
+        w.flush()
+    }
+
+    fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let mut len = [0; 4];
+        r.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as usize;
+        if len == 0 || len > MAX_FRAME {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let mut buf = vec![0; len];
+        r.read_exact(&mut buf)?;
+        let payload = buf.split_off(1);
+        let offset = || -> io::Result<u64> {
+            let bytes = payload.as_slice().try_into();
+            Ok(u64::from_le_bytes(
+                bytes.map_err(|_| io::ErrorKind::InvalidData)?,
+            ))
+        };
+
+        let frame = match buf[0] {
+            TAG_SUBSCRIBE => Frame::Subscribe(offset()?),
+            TAG_REPLICATE => Frame::Replicate(offset()?),
+            TAG_ACK => Frame::Ack(offset()?),
+            TAG_PUBLISH => Frame::Publish(payload),
+            TAG_EVENT => Frame::Event(payload),
+            TAG_REJECT => Frame::Reject(String::from_utf8_lossy(&payload).into_owned()),
+            _ => return Err(io::ErrorKind::InvalidData.into()),
+        };
+
+        Ok(frame)
+    }
+}
+
+type Conn = (BufReader<TcpStream>, BufWriter<TcpStream>);
+
+fn connect(addr: &str) -> io::Result<Conn> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+    Ok((BufReader::new(stream.try_clone()?), BufWriter::new(stream)))
+}
+
+enum Role {
+    Primary { replicas: usize },
+    Follower { primary: String },
+}
+
+/// Durable log length acknowledged by each connected follower.
+#[derive(Default)]
+struct Followers {
+    next_id: u64,
+    acked: HashMap<u64, u64>,
+    /// Primary: length of the log replicated to enough followers to be
+    /// delivered to subscribers.
+    committed: u64,
+}
+
+impl Followers {
+    /// Returns the number of followers that have durably stored `offset`.
+    fn holding(&self, offset: u64) -> usize {
+        self.acked.values().filter(|&&len| len > offset).count()
+    }
+}
+
+/// Connections being served, shut down once the server stops.
+#[derive(Default)]
+struct Connections {
+    next_id: u64,
+    open: HashMap<u64, TcpStream>,
+    stopped: bool,
+}
+
+/// A stream server, either a primary or a follower.
+pub struct Server {
+    log: FileStream,
+    role: Role,
+    followers: Mutex<Followers>,
+    replicated: Condvar,
+    connections: Mutex<Connections>,
+}
+
+impl Server {
+    /// Creates a primary that acknowledges a publish once `replicas`
+    /// followers have durably stored it.
+    ///
+    /// With `replicas` set to zero, events are acknowledged once durable on
+    /// the primary alone.
+    pub fn primary(log: FileStream, replicas: usize) -> Self {
+        Self::new(log, Role::Primary { replicas })
+    }
+
+    /// Creates a follower replicating from the primary at `primary`.
+    ///
+    /// Followers serve subscriptions but reject publishes.
+    pub fn follower(log: FileStream, primary: &str) -> Self {
+        Self::new(
+            log,
+            Role::Follower {
+                primary: primary.to_owned(),
+            },
+        )
+    }
+
+    fn new(log: FileStream, role: Role) -> Self {
+        // Which events were replicated before a restart is unknown, so the
+        // log is delivered as it stands, as a promoted follower's would be.
+        let followers = Followers {
+            committed: log.len(),
+            ..Followers::default()
+        };
+        Self {
+            log,
+            role,
+            followers: Mutex::new(followers),
+            replicated: Condvar::new(),
+            connections: Mutex::default(),
+        }
+    }
+
+    /// Serves connections accepted on `listener`, and replicates from the
+    /// primary if this server is a follower.
+    ///
+    /// Failing to accept a connection is logged to stderr and does not stop
+    /// the server. A primary blocks forever, while a follower returns an
+    /// error once the primary rejects it, after closing its connections.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        let mut addr = listener.local_addr()?;
+        if addr.ip().is_unspecified() {
+            addr.set_ip(match addr {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+
+        thread::scope(|s| {
+            let follower = match &self.role {
+                Role::Follower { primary } => Some(s.spawn(move || {
+                    let reason = self.follow(primary);
+                    self.stop();
+                    // Wake the accept loop so it notices the stop.
+                    let _ = TcpStream::connect(addr);
+                    reason
+                })),
+                Role::Primary { .. } => None,
+            };
+
+            for stream in listener.incoming() {
+                let (id, stream) = match stream.and_then(|stream| self.open(stream)) {
+                    Ok(Some(opened)) => opened,
+                    Ok(None) => break,
+                    Err(e) => {
+                        eprintln!("evcore: failed to accept connection: {e}");
+                        thread::sleep(ACCEPT_DELAY);
+                        continue;
+                    }
+                };
+                s.spawn(move || {
+                    let result = self.handle(stream);
+                    self.connections.lock().unwrap().open.remove(&id);
+                    result
+                });
+            }
+
+            match follower {
+                Some(follower) => Err(io::Error::other(format!(
+                    "primary rejected replication: {}",
+                    follower.join().unwrap()
+                ))),
+                None => Ok(()),
+            }
+        })
+    }
+
+    /// Registers an accepted connection so that stopping can shut it down,
+    /// or returns `None` if the server has stopped.
+    fn open(&self, stream: TcpStream) -> io::Result<Option<(u64, TcpStream)>> {
+        let mut connections = self.connections.lock().unwrap();
+        if connections.stopped {
+            return Ok(None);
+        }
+
+        let id = connections.next_id;
+        connections.next_id += 1;
+        connections.open.insert(id, stream.try_clone()?);
+        Ok(Some((id, stream)))
+    }
+
+    /// Stops serving, shutting down every open connection.
+    fn stop(&self) {
+        let mut connections = self.connections.lock().unwrap();
+        connections.stopped = true;
+        for stream in connections.open.values() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.connections.lock().unwrap().stopped
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        match Frame::read_from(&mut reader)? {
+            Frame::Subscribe(offset) => {
+                self.stream_to(offset, &mut writer, &AtomicBool::new(false), |next| {
+                    self.wait_deliverable(next)
+                })
+            }
+            Frame::Replicate(offset) => self.replicate_to(offset, reader, writer),
+            Frame::Publish(mut data) => loop {
+                self.publish(&data)?.write_to(&mut writer)?;
+                match Frame::read_from(&mut reader)? {
+                    Frame::Publish(next) => data = next,
+                    _ => return Err(io::ErrorKind::InvalidData.into()),
+                }
+            },
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    /// Streams events from `offset` until writing fails, `done` is set or the
+    /// server stops, sending each once `ready` reports it may be sent.
+    fn stream_to(
+        &self,
+        offset: u64,
+        writer: &mut impl Write,
+        done: &AtomicBool,
+        ready: impl Fn(u64) -> bool,
+    ) -> io::Result<()> {
+        let receiver = self.log.subscribe(offset);
+        let mut next = offset;
+        while !done.load(Ordering::Relaxed) && !self.stopped() {
+            if ready(next) {
+                Frame::Event(receiver.recv()).write_to(writer)?;
+                next += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits up to [`POLL_INTERVAL`] for the event at `offset` to be
+    /// deliverable to subscribers, and returns whether it is: on a primary
+    /// once replicated, and on a follower once stored.
+    fn wait_deliverable(&self, offset: u64) -> bool {
+        if let Role::Follower { .. } = self.role {
+            return self.log.wait_for(offset, POLL_INTERVAL);
+        }
+
+        let followers = self.followers.lock().unwrap();
+        let (followers, _) = self
+            .replicated
+            .wait_timeout_while(followers, POLL_INTERVAL, |followers| {
+                followers.committed <= offset
+            })
+            .unwrap();
+        followers.committed > offset
+    }
+
+    fn publish(&self, data: &[u8]) -> io::Result<Frame> {
+        let Role::Primary { replicas } = self.role else {
+            return Ok(Frame::Reject("not a primary".to_owned()));
+        };
+
+        let offset = self.log.append(data)?;
+        let mut followers = self.followers.lock().unwrap();
+        while followers.holding(offset) < replicas {
+            followers = self.replicated.wait(followers).unwrap();
+        }
+
+        // Followers store events in order, so every earlier event is
+        // replicated as well.
+        if followers.committed <= offset {
+            followers.committed = offset + 1;
+            self.replicated.notify_all();
+        }
+
+        Ok(Frame::Ack(offset))
+    }
+
+    fn replicate_to(
+        &self,
+        offset: u64,
+        mut reader: BufReader<TcpStream>,
+        mut writer: BufWriter<TcpStream>,
+    ) -> io::Result<()> {
+        if let Role::Follower { .. } = self.role {
+            return Frame::Reject("not a primary".to_owned()).write_to(&mut writer);
+        }
+        if offset > self.log.len() {
+            return Frame::Reject("follower is ahead of primary".to_owned()).write_to(&mut writer);
+        }
+
+        let id = {
+            let mut followers = self.followers.lock().unwrap();
+            let id = followers.next_id;
+            followers.next_id += 1;
+            followers.acked.insert(id, offset);
+            id
+        };
+
+        let done = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                // A failed write shuts the socket down, ending the loop
+                // reading acknowledgments below.
+                let result = self.stream_to(offset, &mut writer, &done, |next| {
+                    self.log.wait_for(next, POLL_INTERVAL)
+                });
+                let _ = writer.get_ref().shutdown(Shutdown::Both);
+                result
+            });
+
+            let result = loop {
+                match Frame::read_from(&mut reader) {
+                    Ok(Frame::Ack(len)) => {
+                        self.followers.lock().unwrap().acked.insert(id, len);
+                        self.replicated.notify_all();
+                    }
+                    Ok(_) => break Err(io::ErrorKind::InvalidData.into()),
+                    Err(e) => break Err(e),
+                }
+            };
+
+            self.followers.lock().unwrap().acked.remove(&id);
+            done.store(true, Ordering::Relaxed);
+            let _ = reader.get_ref().shutdown(Shutdown::Both);
+            result
+        })
+    }
+
+    /// Replicates from `primary`, reconnecting whenever the connection
+    /// fails, and returns the primary's reason once it rejects this follower.
+    fn follow(&self, primary: &str) -> String {
+        loop {
+            match self.follow_once(primary) {
+                Ok(reason) => return reason,
+                Err(_) => thread::sleep(RETRY_DELAY),
+            }
+        }
+    }
+
+    /// Replicates until the connection fails, or returns the primary's reason
+    /// for rejecting this follower.
+    fn follow_once(&self, primary: &str) -> io::Result<String> {
+        let (mut reader, mut writer) = connect(primary)?;
+        Frame::Replicate(self.log.len()).write_to(&mut writer)?;
+
+        loop {
+            match Frame::read_from(&mut reader)? {
+                Frame::Event(data) => {
+                    let offset = self.log.append(&data)?;
+                    Frame::Ack(offset + 1).write_to(&mut writer)?;
+                }
+                Frame::Reject(reason) => return Ok(reason),
+                _ => return Err(io::ErrorKind::InvalidData.into()),
+            }
+        }
+    }
+}
+
+/// [`Stream`] client for a remote [`Server`].
+///
+/// Subscriptions may be served by the primary or any follower.
+pub struct RemoteStream {
+    addr: String,
+}
+
+impl RemoteStream {
+    /// Creates a client for the server at `addr`.
+    pub fn new(addr: &str) -> Self {
+        Self {
+            addr: addr.to_owned(),
+        }
+    }
+
+    /// Creates a producer publishing through the server at this address,
+    /// which must be the primary.
+    pub fn producer(&self) -> RemoteProducer {
+        RemoteProducer {
+            addr: self.addr.clone(),
+            conn: Mutex::new(None),
+        }
+    }
+}
+
+impl Stream for RemoteStream {
+    type Receiver = RemoteReceiver;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        RemoteReceiver {
+            addr: self.addr.clone(),
+            state: Mutex::new((None, offset)),
+        }
+    }
+}
+
+/// Receiver streaming events from a remote [`Server`].
+///
+/// If the connection fails, the receiver reconnects and resubscribes at the
+/// next undelivered offset.
+pub struct RemoteReceiver {
+    addr: String,
+    state: Mutex<(Option<BufReader<TcpStream>>, u64)>,
+}
+
+impl Receiver for RemoteReceiver {
+    fn recv(&self) -> Vec<u8> {
+        let mut state = self.state.lock().unwrap();
+        let (conn, next) = &mut *state;
+
+        loop {
+            let frame = match conn {
+                Some(reader) => Frame::read_from(reader),
+                None => connect(&self.addr).and_then(|(reader, mut writer)| {
+                    Frame::Subscribe(*next).write_to(&mut writer)?;
+                    Frame::read_from(conn.insert(reader))
+                }),
+            };
+
+            match frame {
+                Ok(Frame::Event(data)) => {
+                    *next += 1;
+                    return data;
+                }
+                _ => {
+                    *conn = None;
+                    thread::sleep(RETRY_DELAY);
+                }
+            }
+        }
+    }
+}
+
+/// [`Producer`] publishing through a remote primary [`Server`].
+///
+/// Each publish blocks until the primary acknowledges that the event is
+/// replicated. A publish interrupted by a connection failure is retried and
+/// may therefore be stored twice. Panics if the server rejects the publish,
+/// for example because it is not a primary.
+pub struct RemoteProducer {
+    addr: String,
+    conn: Mutex<Option<Conn>>,
+}
+
+impl Producer for RemoteProducer {
+    fn publish(&self, data: &[u8]) {
+        let mut conn = self.conn.lock().unwrap();
+
+        loop {
+            let frame = match conn.as_mut() {
+                Some(conn) => Ok(conn),
+                None => connect(&self.addr).map(|c| conn.insert(c)),
+            }
+            .and_then(|(reader, writer)| {
+                Frame::Publish(data.to_vec()).write_to(writer)?;
+                Frame::read_from(reader)
+            });
+
+            match frame {
+                Ok(Frame::Ack(_)) => return,
+                Ok(Frame::Reject(reason)) => panic!("stream server rejected publish: {reason}"),
+                _ => {
+                    *conn = None;
+                    thread::sleep(RETRY_DELAY);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, fs, path::PathBuf, process, time::Instant};
+
+    fn log_file(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("evcore-replication-{name}-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Serves `server` on a free localhost port, returning its address.
+    fn spawn(server: Server) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || server.serve(listener));
+        addr
+    }
+
+    #[test]
+    fn follower_replicates_published_events() {
+        let (primary_log, follower_log) = (log_file("primary"), log_file("follower"));
+        let primary = spawn(Server::primary(FileStream::open(&primary_log).unwrap(), 1));
+        let follower = spawn(Server::follower(
+            FileStream::open(&follower_log).unwrap(),
+            &primary,
+        ));
+
+        let events: Vec<_> = (0..20).map(|i| format!("event {i}").into_bytes()).collect();
+        let producer = RemoteStream::new(&primary).producer();
+        for event in &events {
+            producer.publish(event);
+        }
+
+        for addr in [&primary, &follower] {
+            let receiver = RemoteStream::new(addr).subscribe(0);
+            for event in &events {
+                assert_eq!(receiver.recv(), *event);
+            }
+        }
+        let _ = fs::remove_file(&primary_log);
+        let _ = fs::remove_file(&follower_log);
+    }
+
+    #[test]
+    fn primary_delivers_only_replicated_events() {
+        use std::sync::mpsc;
+
+        let (primary_log, follower_log) = (log_file("unreplicated"), log_file("late-follower"));
+        let primary = spawn(Server::primary(FileStream::open(&primary_log).unwrap(), 1));
+
+        let (delivered, received) = mpsc::channel();
+        let receiver = RemoteStream::new(&primary).subscribe(0);
+        thread::spawn(move || delivered.send(receiver.recv()));
+        let producer = RemoteStream::new(&primary).producer();
+        let publish = thread::spawn(move || producer.publish(b"event"));
+
+        thread::sleep(Duration::from_millis(300));
+        assert!(!publish.is_finished(), "acknowledged without a follower");
+        assert!(
+            received.try_recv().is_err(),
+            "delivered before it was replicated"
+        );
+
+        spawn(Server::follower(
+            FileStream::open(&follower_log).unwrap(),
+            &primary,
+        ));
+        publish.join().unwrap();
+        let event = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, b"event");
+
+        let _ = fs::remove_file(&primary_log);
+        let _ = fs::remove_file(&follower_log);
+    }
+
+    #[test]
+    fn rejected_follower_stops_serving() {
+        use std::sync::mpsc;
+
+        let (primary_log, follower_log) = (log_file("behind"), log_file("ahead"));
+        let primary = spawn(Server::primary(FileStream::open(&primary_log).unwrap(), 0));
+        let log = FileStream::open(&follower_log).unwrap();
+        log.append(b"not on the primary").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (served, result) = mpsc::channel();
+        thread::spawn(move || served.send(Server::follower(log, &primary).serve(listener)));
+
+        // A subscriber left waiting must not keep the follower serving.
+        let subscriber = connect(&addr);
+        let error = result
+            .recv_timeout(Duration::from_secs(5))
+            .expect("follower kept serving")
+            .unwrap_err();
+        assert!(error.to_string().contains("follower is ahead of primary"));
+        drop(subscriber);
+
+        let _ = fs::remove_file(&primary_log);
+        let _ = fs::remove_file(&follower_log);
+    }
+
+    #[test]
+    fn disconnected_follower_is_forgotten() {
+        let path = log_file("disconnect");
+        let server: &'static Server = Box::leak(Box::new(Server::primary(
+            FileStream::open(&path).unwrap(),
+            0,
+        )));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || server.serve(listener));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let registered = || !server.followers.lock().unwrap().acked.is_empty();
+
+        let (_, mut writer) = connect(&addr).unwrap();
+        Frame::Replicate(0).write_to(&mut writer).unwrap();
+        while !registered() {
+            assert!(Instant::now() < deadline, "follower never registered");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        drop(writer);
+        while registered() {
+            assert!(Instant::now() < deadline, "follower never removed");
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -5,25 +5,84 @@
 //! one sequencer can be active at a time, enforced through leader election.
 
 use crate::{
-    consumer,
+    clock::{Clock, MonotonicClock},
+    consumer::{self, Divergence},
     election::Election,
     inbox::Inbox,
     logic::Logic,
+    observer::Observer,
     stream::{Producer, Stream},
 };
 
 use std::{
-    process,
-    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    error, fmt, process,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
     thread,
-    time::Duration,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
 const STATUS_STARTING: usize = 0;
 const STATUS_CAUGHT_UP: usize = 1;
 const STATUS_LEADER: usize = 2;
 const STATUS_ACTIVATED: usize = 3;
+const STATUS_STOPPED: usize = 4;
+
+/// Phase of a running sequencer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// Consuming the stream to rebuild state.
+    Starting,
+    /// Caught up with the stream and contending for leadership.
+    CaughtUp,
+    /// Holding leadership and publishing activation events until one is
+    /// observed.
+    Leader,
+    /// Activation observed; processing commands from the inbox.
+    Activated,
+    /// The run has returned, having stepped down, been shut down, lost its
+    /// lease under [`LostLease::Stop`] or diverged. It no longer leads or
+    /// contends for leadership.
+    Stopped,
+}
+
+impl Status {
+    /// Returns the lowercase name of the phase, as used in metrics labels and
+    /// health endpoints.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Starting => "starting",
+            Status::CaughtUp => "caught_up",
+            Status::Leader => "leader",
+            Status::Activated => "activated",
+            Status::Stopped => "stopped",
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "health", feature = "metrics")), allow(dead_code))]
+    pub(crate) fn to_usize(self) -> usize {
+        match self {
+            Status::Starting => STATUS_STARTING,
+            Status::CaughtUp => STATUS_CAUGHT_UP,
+            Status::Leader => STATUS_LEADER,
+            Status::Activated => STATUS_ACTIVATED,
+            Status::Stopped => STATUS_STOPPED,
+        }
+    }
+
+    pub(crate) fn from_usize(status: usize) -> Self {
+        match status {
+            STATUS_STARTING => Status::Starting,
+            STATUS_CAUGHT_UP => Status::CaughtUp,
+            STATUS_LEADER => Status::Leader,
+            STATUS_ACTIVATED => Status::Activated,
+            STATUS_STOPPED => Status::Stopped,
+            _ => unreachable!(),
+        }
+    }
+}
 
 /// A function that produces an event for the sequencer.
 pub trait EventGenerator: Fn() -> Vec<u8> + Send + Sync {}
@@ -31,6 +90,186 @@ pub trait EventGenerator: Fn() -> Vec<u8> + Send + Sync {}
 // Blanket impl: any closure or fn matching the signature automatically implements EventMaker.
 impl<T: Fn() -> Vec<u8> + Send + Sync> EventGenerator for T {}
 
+/// A function that produces an event stamped with a time since the Unix
+/// epoch.
+pub trait StampedGenerator: Fn(Duration) -> Vec<u8> + Send + Sync {}
+
+impl<T: Fn(Duration) -> Vec<u8> + Send + Sync> StampedGenerator for T {}
+
+/// Marks a command loop that is paused, and so exempt from the progress
+/// deadline until it checks in again.
+const PAUSED: u64 = u64::MAX;
+
+/// Shared state behind a [`SequencerHandle`].
+struct Control {
+    status: AtomicUsize,
+    /// One past the offset of the last applied event; `0` if none.
+    applied: AtomicU64,
+    /// Unix time of the last heartbeat in nanoseconds; `0` if none.
+    heartbeat: AtomicU64,
+    /// Clock reading in nanoseconds when the command loop last checked in;
+    /// [`PAUSED`] while it waits to be resumed.
+    progress: AtomicU64,
+    snapshot: AtomicBool,
+    stopped: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl Control {
+    fn stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        let _paused = self.paused.lock().unwrap();
+        self.resumed.notify_all();
+    }
+
+    /// Blocks while command processing is paused and the sequencer has not
+    /// been stopped.
+    fn wait_resumed(&self) {
+        let mut paused = self.paused.lock().unwrap();
+        if *paused {
+            self.progress.store(PAUSED, Ordering::Relaxed);
+        }
+        while *paused && !self.stopped() {
+            paused = self.resumed.wait(paused).unwrap();
+        }
+    }
+
+    fn applied(&self, count: u64) {
+        self.applied.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Records that the command loop made progress at `now`.
+    fn check_in(&self, now: Duration) {
+        self.progress
+            .store(now.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the command loop, unless paused, has not checked in
+    /// within `deadline` of `now`.
+    fn stalled(&self, now: Duration, deadline: Option<Duration>) -> bool {
+        match (deadline, self.progress.load(Ordering::Relaxed)) {
+            (None, _) | (_, PAUSED) => false,
+            (Some(deadline), progress) => {
+                now.saturating_sub(Duration::from_nanos(progress)) > deadline
+            }
+        }
+    }
+}
+
+/// Controls and inspects a sequencer run from other threads.
+///
+/// Pass a handle to [`SequencerBuilder::handle`]; clones share the same
+/// sequencer.
+/// Requests take effect at the sequencer's next opportunity: between events
+/// while replaying, between commands once activated, and at the next tick
+/// of the election loop. A standby on a quiet stream only notices a request
+/// to stop when the next event arrives.
+///
+/// A handle controls a single run. Once stopped, later runs under the same
+/// handle return immediately.
+#[derive(Clone)]
+pub struct SequencerHandle {
+    inner: Arc<Control>,
+}
+
+impl Default for SequencerHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SequencerHandle {
+    /// Creates a handle for a sequencer that has not started yet.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Control {
+                status: AtomicUsize::new(STATUS_STARTING),
+                applied: AtomicU64::new(0),
+                heartbeat: AtomicU64::new(0),
+                progress: AtomicU64::new(0),
+                snapshot: AtomicBool::new(false),
+                stopped: AtomicBool::new(false),
+                paused: Mutex::new(false),
+                resumed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Returns the current phase.
+    pub fn status(&self) -> Status {
+        Status::from_usize(self.inner.status.load(Ordering::Relaxed))
+    }
+
+    /// Returns the offset of the last event applied to the logic, or `None`
+    /// if none has been.
+    ///
+    /// Offsets are counted from the one returned by [`Logic::load`]. Once
+    /// activated, the sequencer applies the events it publishes without
+    /// reading them back, so copies of its activation event published while
+    /// it waited to observe the first one are not counted.
+    pub fn last_applied(&self) -> Option<u64> {
+        self.inner.applied.load(Ordering::Relaxed).checked_sub(1)
+    }
+
+    /// Returns when the last heartbeat was published to the stream, or sent
+    /// to the inbox if the sequencer publishes no [stream heartbeats], or
+    /// `None` if the sequencer has not been activated.
+    ///
+    /// [stream heartbeats]: Sequencer::stream_heartbeat
+    pub fn last_heartbeat(&self) -> Option<SystemTime> {
+        match self.inner.heartbeat.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// Returns `true` if command processing is paused.
+    pub fn is_paused(&self) -> bool {
+        *self.inner.paused.lock().unwrap()
+    }
+
+    /// Pauses command processing. Commands wait in the inbox, and the
+    /// sequencer keeps renewing its lease.
+    pub fn pause(&self) {
+        *self.inner.paused.lock().unwrap() = true;
+    }
+
+    /// Resumes command processing after [`pause`](Self::pause).
+    pub fn resume(&self) {
+        *self.inner.paused.lock().unwrap() = false;
+        self.inner.resumed.notify_all();
+    }
+
+    /// Requests a call to [`Logic::save_snapshot`] at the next opportunity.
+    pub fn snapshot(&self) {
+        self.inner.snapshot.store(true, Ordering::Relaxed);
+    }
+
+    /// Gives up leadership: the sequencer stops processing commands and
+    /// renewing its lease, [resigns](Election::resign), and
+    /// [`run`](SequencerBuilder::run) returns. Another sequencer takes over
+    /// at its next election, or once the lease expires if the election
+    /// cannot resign.
+    ///
+    /// Has no effect if the sequencer is not leading.
+    pub fn step_down(&self) {
+        if matches!(self.status(), Status::Leader | Status::Activated) {
+            self.inner.stop();
+        }
+    }
+
+    /// Stops the sequencer in whatever phase it is in, making
+    /// [`run`](SequencerBuilder::run) return.
+    pub fn shutdown(&self) {
+        self.inner.stop();
+    }
+}
+
 /// Logic for processing commands into events.
 ///
 /// A [`Sequencer`] extends [`Logic`] with the ability to receive commands,
@@ -59,49 +298,184 @@ pub trait Sequencer: Logic {
 
     /// Returns the heartbeat function for this sequencer.
     ///
-    /// The heartbeat function is used to keep the sequencer alive by publishing
-    /// a heartbeat event to the stream.
+    /// The heartbeat function produces a command that the activated
+    /// sequencer sends to its own inbox after each lease renewal. It reaches
+    /// the stream only if [`process`](Sequencer::process) turns it into an
+    /// event; see [`stream_heartbeat`](Sequencer::stream_heartbeat) for
+    /// heartbeats that always do.
     fn heartbeat(&self) -> Box<dyn EventGenerator>;
 
+    /// Returns the function producing heartbeat events, or `None` to publish
+    /// none.
+    ///
+    /// The activated sequencer publishes a heartbeat event straight to the
+    /// stream every heartbeat interval, stamped with its wall-clock time, so
+    /// that consumers can tell how recently the leader was alive. Heartbeat
+    /// events must be recognized by [`read_heartbeat`](Logic::read_heartbeat)
+    /// and leave state unchanged when stepped, since the sequencer publishes
+    /// them without applying them.
+    ///
+    /// These are the heartbeats a consumer's [watchdog] relies on to tell an
+    /// idle leader from a dead one; see
+    /// [`SequencerBuilder::liveness_window`].
+    ///
+    /// [watchdog]: crate::consumer::ConsumerBuilder::watchdog
+    fn stream_heartbeat(&self) -> Option<Box<dyn StampedGenerator>> {
+        None
+    }
+
     /// Returns `true` if the event is this sequencer's own activation event.
     ///
     /// Used to detect when the activation event published by this sequencer
     /// has been committed to the stream, signaling it can begin processing.
     fn is_activation(&self, event: &[u8]) -> bool;
+
+    /// Returns a checksum event to publish after the event just produced, or
+    /// `None` if none is due.
+    ///
+    /// A checksum event records [`state_hash`](Logic::state_hash) so that
+    /// consumers can detect divergence; it must be recognized by
+    /// [`read_checksum`](Logic::read_checksum). Implementations typically
+    /// emit one every fixed number of sequence numbers.
+    fn checksum(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Hooks through which the sequencer manages its threads and exits.
+///
+/// [`SequencerBuilder::run`] uses real threads and [`process::exit`]; the
+/// simulation harness substitutes virtual equivalents so several sequencers
+/// can run deterministically within one process.
+pub(crate) trait Env: Clock {
+    /// Terminates the sequencer after it loses leadership.
+    fn exit(&self) -> !;
+
+    /// Registers a thread about to be spawned, returning a ticket that the
+    /// new thread passes to [`Env::enter`].
+    fn fork(&self) -> usize {
+        0
+    }
+
+    /// Runs `f` on behalf of the thread registered under `ticket`.
+    fn enter(&self, _ticket: usize, f: &mut dyn FnMut()) {
+        f()
+    }
+
+    /// Returns the wall-clock time since the Unix epoch, used to stamp
+    /// heartbeats.
+    fn timestamp(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
 }
 
-struct Wrapper<'a, S> {
+/// The environment of a real process, timed by the given clock.
+struct System<'a, C>(&'a C);
+
+impl<C: Clock> Clock for System<'_, C> {
+    fn now(&self) -> Duration {
+        self.0.now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.0.sleep(duration);
+    }
+}
+
+impl<C: Clock> Env for System<'_, C> {
+    fn exit(&self) -> ! {
+        process::exit(1)
+    }
+}
+
+/// The current status, reporting every transition to the observer.
+struct Phase<'a, O> {
     status: &'a AtomicUsize,
+    observer: &'a O,
+}
+
+impl<O: Observer> Phase<'_, O> {
+    fn load(&self) -> usize {
+        self.status.load(Ordering::Relaxed)
+    }
+
+    /// Moves to `to` if the current status is `from`.
+    fn advance(&self, from: usize, to: usize) {
+        if self
+            .status
+            .compare_exchange(from, to, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.notify(from, to);
+        }
+    }
+
+    /// Moves to `to` from whatever the current status is.
+    fn set(&self, to: usize) {
+        let from = self.status.swap(to, Ordering::Relaxed);
+        if from != to {
+            self.notify(from, to);
+        }
+    }
+
+    fn notify(&self, from: usize, to: usize) {
+        self.observer
+            .status(Status::from_usize(from), Status::from_usize(to));
+    }
+}
+
+struct Wrapper<'a, S, V, O> {
+    env: &'a V,
+    phase: &'a Phase<'a, O>,
+    control: &'a Control,
+    catch_up: CatchUp,
     last_step: &'a AtomicU64,
     logic: &'a mut S,
 }
 
-impl<'a, S: Sequencer> Wrapper<'a, S> {
+impl<'a, S: Sequencer, V: Env, O: Observer> Wrapper<'a, S, V, O> {
     fn check_caught_up(&mut self) {
-        if self.logic.caught_up() {
-            self.status.store(STATUS_CAUGHT_UP, Ordering::Relaxed);
+        if self.catch_up.uses_logic() && self.logic.caught_up() {
+            self.phase.advance(STATUS_STARTING, STATUS_CAUGHT_UP);
         }
     }
 }
 
-impl<S: Sequencer> Logic for Wrapper<'_, S> {
+impl<S: Sequencer, V: Env, O: Observer> Logic for Wrapper<'_, S, V, O> {
     fn load(&mut self) -> u64 {
         let offset = self.logic.load();
+        self.control.applied.store(offset, Ordering::Relaxed);
         self.check_caught_up();
 
         offset
     }
 
     fn step(&mut self, event: &[u8]) -> bool {
+        if self.control.stopped() {
+            return false;
+        }
         self.check_caught_up();
 
+        let start = self.env.now();
         let cont = self.logic.step(event);
+        let now = self.env.now();
+        self.control.applied(1);
+        self.phase.observer.step(event, now.saturating_sub(start));
+
+        if self.control.snapshot.swap(false, Ordering::Relaxed) {
+            self.logic.save_snapshot();
+        }
+
         if self.logic.is_activation(event) {
-            self.status.store(STATUS_ACTIVATED, Ordering::Relaxed);
+            self.control.check_in(now);
+            self.phase.set(STATUS_ACTIVATED);
             return false;
         }
 
-        self.last_step.store(now(), Ordering::Relaxed);
+        self.last_step
+            .store(now.as_nanos() as u64, Ordering::Relaxed);
 
         cont
     }
@@ -109,6 +483,463 @@ impl<S: Sequencer> Logic for Wrapper<'_, S> {
     fn caught_up(&mut self) -> bool {
         self.logic.caught_up()
     }
+
+    fn state_hash(&self) -> Option<u64> {
+        self.logic.state_hash()
+    }
+
+    fn read_checksum(&self, event: &[u8]) -> Option<u64> {
+        self.logic.read_checksum(event)
+    }
+
+    fn read_heartbeat(&self, event: &[u8]) -> Option<Duration> {
+        self.logic.read_heartbeat(event)
+    }
+
+    fn save_snapshot(&mut self) {
+        self.logic.save_snapshot();
+    }
+}
+
+/// How the sequencer decides it has caught up with the stream and may
+/// contend for leadership.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CatchUp {
+    /// Once [`Logic::caught_up`] returns `true`.
+    Logic,
+    /// Once no event has arrived for the given window.
+    Idle(Duration),
+    /// Whichever of [`Logic`](CatchUp::Logic) and [`Idle`](CatchUp::Idle)
+    /// happens first.
+    LogicOrIdle(Duration),
+}
+
+impl CatchUp {
+    fn uses_logic(&self) -> bool {
+        matches!(self, CatchUp::Logic | CatchUp::LogicOrIdle(_))
+    }
+
+    fn idle(&self) -> Option<Duration> {
+        match self {
+            CatchUp::Logic => None,
+            CatchUp::Idle(window) | CatchUp::LogicOrIdle(window) => Some(*window),
+        }
+    }
+}
+
+/// What the sequencer does when it fails to renew its lease.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LostLease {
+    /// Exits the process with status `1`. Nothing more can be published, so
+    /// this is the only policy that rules out a split brain.
+    Exit,
+    /// Stops the sequencer as [`SequencerHandle::shutdown`] would, so that
+    /// the run returns.
+    ///
+    /// Commands already processed are published only while the lease, less
+    /// the [margin](SequencerBuilder::lease_margin), has not run out, and
+    /// are dropped otherwise. The returned logic may therefore be ahead of
+    /// the stream, having applied events that were never published, and
+    /// must be rebuilt from the stream before it is run again.
+    Stop,
+}
+
+/// A [`SequencerBuilder`] or [`ConsumerBuilder`] option with an invalid
+/// value.
+///
+/// [`ConsumerBuilder`]: crate::consumer::ConsumerBuilder
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidOption {
+    /// Name of the option.
+    pub option: &'static str,
+    /// Why the value is invalid.
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}: {}", self.option, self.reason)
+    }
+}
+
+impl error::Error for InvalidOption {}
+
+/// Why [`SequencerBuilder::run`] failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunError {
+    /// An option has an invalid value, so the sequencer never started.
+    InvalidOption(InvalidOption),
+    /// A checksum event read while consuming the stream disagreed with the
+    /// logic's state. The sequencer stopped rather than lead with state
+    /// that differs from its predecessor's, resigning if it had been
+    /// elected.
+    Diverged(Divergence),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::InvalidOption(invalid) => invalid.fmt(f),
+            RunError::Diverged(divergence) => divergence.fmt(f),
+        }
+    }
+}
+
+impl error::Error for RunError {}
+
+impl From<InvalidOption> for RunError {
+    fn from(invalid: InvalidOption) -> Self {
+        RunError::InvalidOption(invalid)
+    }
+}
+
+/// Settings of a sequencer run.
+#[derive(Clone, Debug)]
+pub(crate) struct Options {
+    pub(crate) election_interval: Duration,
+    pub(crate) renew_interval: Duration,
+    pub(crate) activation_interval: Duration,
+    pub(crate) heartbeat_interval: Duration,
+    pub(crate) catch_up: CatchUp,
+    pub(crate) lost_lease: LostLease,
+    pub(crate) batch: usize,
+    pub(crate) progress_deadline: Option<Duration>,
+    pub(crate) lease_margin: Duration,
+    pub(crate) liveness_window: Option<Duration>,
+}
+
+impl Options {
+    /// The settings of the positional runners, which pace every task of the
+    /// election loop with one `interval`.
+    pub(crate) fn uniform(interval: Duration, wait_for: Duration) -> Self {
+        Self {
+            election_interval: interval,
+            renew_interval: interval,
+            activation_interval: interval,
+            heartbeat_interval: interval,
+            catch_up: CatchUp::LogicOrIdle(wait_for),
+            lost_lease: LostLease::Exit,
+            batch: 1,
+            progress_deadline: None,
+            lease_margin: Duration::ZERO,
+            liveness_window: None,
+        }
+    }
+
+    fn validate(&self) -> Result<(), InvalidOption> {
+        let zero = |option| InvalidOption {
+            option,
+            reason: "must be greater than zero",
+        };
+
+        for (option, value) in [
+            ("election_interval", self.election_interval),
+            ("renew_interval", self.renew_interval),
+            ("activation_interval", self.activation_interval),
+            ("heartbeat_interval", self.heartbeat_interval),
+        ] {
+            if value.is_zero() {
+                return Err(zero(option));
+            }
+        }
+        if self.catch_up.idle().is_some_and(|window| window.is_zero()) {
+            return Err(zero("catch_up"));
+        }
+        if self.batch == 0 {
+            return Err(zero("batch"));
+        }
+        if let Some(deadline) = self.progress_deadline
+            && deadline <= self.renew_interval
+        {
+            return Err(InvalidOption {
+                option: "progress_deadline",
+                reason: "must be longer than renew_interval",
+            });
+        }
+        if let Some(window) = self.liveness_window
+            && window < self.heartbeat_interval * 2
+        {
+            return Err(InvalidOption {
+                option: "liveness_window",
+                reason: "must be at least twice heartbeat_interval",
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that the lease margin and renew interval fit within the
+    /// election's `lease`, if it reports one.
+    fn validate_lease(&self, lease: Option<Duration>) -> Result<(), InvalidOption> {
+        let Some(lease) = lease else {
+            return Ok(());
+        };
+        if self.lease_margin >= lease {
+            return Err(InvalidOption {
+                option: "lease_margin",
+                reason: "must be shorter than the election's lease",
+            });
+        }
+        if self.renew_interval >= lease - self.lease_margin {
+            return Err(InvalidOption {
+                option: "renew_interval",
+                reason: "must be shorter than the lease less lease_margin",
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Configures and runs a sequencer with named options.
+///
+/// ```ignore
+/// let logic = SequencerBuilder::new()
+///     .renew_interval(Duration::from_millis(100))
+///     .catch_up(CatchUp::Idle(Duration::from_secs(1)))
+///     .batch(64)
+///     .observer(&metrics)
+///     .handle(handle.clone())
+///     .run(&stream, &producer, &inbox, &election, logic)?;
+/// ```
+///
+/// Every option has a default, so only the ones that matter need setting.
+/// [`run`](SequencerBuilder::run) validates the options before starting.
+pub struct SequencerBuilder<C = MonotonicClock, O = ()> {
+    pub(crate) options: Options,
+    clock: C,
+    observer: O,
+    handle: Option<SequencerHandle>,
+}
+
+impl Default for SequencerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SequencerBuilder {
+    /// Creates a builder with the default options: every interval at 100ms
+    /// except a one-second heartbeat, catch-up by [`Logic::caught_up`] or a
+    /// one-second idle window, [`LostLease::Exit`], no batching, a
+    /// [`MonotonicClock`] and no observer.
+    pub fn new() -> Self {
+        Self {
+            options: Options {
+                election_interval: Duration::from_millis(100),
+                renew_interval: Duration::from_millis(100),
+                activation_interval: Duration::from_millis(100),
+                heartbeat_interval: Duration::from_secs(1),
+                catch_up: CatchUp::LogicOrIdle(Duration::from_secs(1)),
+                lost_lease: LostLease::Exit,
+                batch: 1,
+                progress_deadline: None,
+                lease_margin: Duration::ZERO,
+                liveness_window: None,
+            },
+            clock: MonotonicClock::new(),
+            observer: (),
+            handle: None,
+        }
+    }
+}
+
+impl<C: Clock, O: Observer> SequencerBuilder<C, O> {
+    /// Sets how often a caught-up sequencer contends for leadership, and how
+    /// often a starting one checks whether it has caught up.
+    pub fn election_interval(mut self, interval: Duration) -> Self {
+        self.options.election_interval = interval;
+        self
+    }
+
+    /// Sets how often the leader renews its lease. Must be comfortably
+    /// shorter than the lease itself.
+    pub fn renew_interval(mut self, interval: Duration) -> Self {
+        self.options.renew_interval = interval;
+        self
+    }
+
+    /// Sets how often a new leader republishes its activation event until
+    /// it observes one on the stream.
+    pub fn activation_interval(mut self, interval: Duration) -> Self {
+        self.options.activation_interval = interval;
+        self
+    }
+
+    /// Sets how often the activated sequencer publishes a heartbeat event to
+    /// the stream, if it [produces them](Sequencer::stream_heartbeat).
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.options.heartbeat_interval = interval;
+        self
+    }
+
+    /// Sets how the sequencer decides it has caught up with the stream.
+    pub fn catch_up(mut self, catch_up: CatchUp) -> Self {
+        self.options.catch_up = catch_up;
+        self
+    }
+
+    /// Sets what happens when the lease cannot be renewed.
+    pub fn lost_lease(mut self, policy: LostLease) -> Self {
+        self.options.lost_lease = policy;
+        self
+    }
+
+    /// Sets the maximum number of commands processed before their events
+    /// are published together with [`Producer::publish_batch`].
+    ///
+    /// Only commands already waiting in the inbox are batched, so a batch
+    /// never waits for more to arrive. Batching needs an inbox that reports
+    /// its [`depth`](Inbox::depth); others are processed one at a time.
+    pub fn batch(mut self, batch: usize) -> Self {
+        self.options.batch = batch;
+        self
+    }
+
+    /// Makes the lease depend on the command loop: once activated, the
+    /// sequencer stops renewing its lease, and handles it as lost, if the
+    /// loop has not checked in within `deadline`.
+    ///
+    /// The loop checks in on every iteration and on receiving each command,
+    /// before [`process`](Sequencer::process) sees it. An idle loop still
+    /// checks in, since every renewal sends it a
+    /// [heartbeat](Sequencer::heartbeat), even if `process` discards it.
+    /// The deadline must therefore be longer than the renew interval, with
+    /// room for a heartbeat to pass through the inbox. A paused loop is not
+    /// held to it.
+    pub fn progress_deadline(mut self, deadline: Duration) -> Self {
+        self.options.progress_deadline = Some(deadline);
+        self
+    }
+
+    /// Sets how long before the locally tracked [lease] expires the
+    /// sequencer stops publishing, handling the lease as lost instead. The
+    /// margin covers clock drift between this host and the election
+    /// backend. It must be shorter than the lease, and the renew interval
+    /// must fit within the lease less it, or [`run`](SequencerBuilder::run)
+    /// fails.
+    ///
+    /// Has no effect if the election reports no lease. Defaults to zero,
+    /// which still stops a leader whose renewals stalled past the lease.
+    ///
+    /// [lease]: Election::lease
+    pub fn lease_margin(mut self, margin: Duration) -> Self {
+        self.options.lease_margin = margin;
+        self
+    }
+
+    /// Declares that consumers watch this sequencer with a [watchdog] of
+    /// `window`.
+    ///
+    /// The watchdog counts every event on the stream, so an idle leader
+    /// looks alive only through the heartbeats it publishes to the stream
+    /// with [`Sequencer::stream_heartbeat`]; the inbox [heartbeat] never
+    /// reaches consumers unless processed into an event. With a window set,
+    /// [`run`](SequencerBuilder::run) fails if the logic publishes no stream
+    /// heartbeats, and the heartbeat interval must be at most half the
+    /// window.
+    ///
+    /// [watchdog]: crate::consumer::ConsumerBuilder::watchdog
+    /// [heartbeat]: Sequencer::heartbeat
+    pub fn liveness_window(mut self, window: Duration) -> Self {
+        self.options.liveness_window = Some(window);
+        self
+    }
+
+    /// Sets the clock that measures the catch-up window and paces the
+    /// election loop.
+    pub fn clock<D: Clock>(self, clock: D) -> SequencerBuilder<D, O> {
+        SequencerBuilder {
+            options: self.options,
+            clock,
+            observer: self.observer,
+            handle: self.handle,
+        }
+    }
+
+    /// Sets the observer that the run reports its progress to.
+    pub fn observer<P: Observer>(self, observer: P) -> SequencerBuilder<C, P> {
+        SequencerBuilder {
+            options: self.options,
+            clock: self.clock,
+            observer,
+            handle: self.handle,
+        }
+    }
+
+    /// Sets the handle that controls the run.
+    pub fn handle(mut self, handle: SequencerHandle) -> Self {
+        self.handle = Some(handle);
+        self
+    }
+
+    /// Checks that every option has a usable value.
+    pub fn validate(&self) -> Result<(), InvalidOption> {
+        self.options.validate()
+    }
+
+    /// Checks the options, and that they suit the election and logic of a
+    /// run.
+    pub(crate) fn check<E: Election, L: Sequencer>(
+        &self,
+        election: &E,
+        logic: &L,
+    ) -> Result<(), InvalidOption> {
+        self.validate()?;
+        self.options.validate_lease(election.lease())?;
+        if self.options.liveness_window.is_some() && logic.stream_heartbeat().is_none() {
+            return Err(InvalidOption {
+                option: "liveness_window",
+                reason: "requires the logic to publish stream heartbeats",
+            });
+        }
+        Ok(())
+    }
+
+    /// Splits the builder into what a run uses besides its clock.
+    #[cfg(feature = "sim")]
+    pub(crate) fn into_parts(self) -> (Options, O, SequencerHandle) {
+        (self.options, self.observer, self.handle.unwrap_or_default())
+    }
+
+    /// Validates the options and runs the sequencer loop, returning the
+    /// logic once the handle stops it or the lease is lost under
+    /// [`LostLease::Stop`].
+    ///
+    /// Fails without running if an option is invalid, if the lease margin
+    /// and renew interval do not fit within the election's lease, or if a
+    /// [liveness window](SequencerBuilder::liveness_window) is set but the
+    /// logic publishes no stream heartbeats. Stops with an error if the
+    /// stream diverges from the logic's state.
+    pub fn run<S, P, I, E, L>(
+        self,
+        stream: &S,
+        producer: &P,
+        inbox: &I,
+        election: &E,
+        logic: L,
+    ) -> Result<L, RunError>
+    where
+        S: Stream,
+        P: Producer,
+        I: Inbox,
+        E: Election,
+        L: Sequencer,
+    {
+        self.check(election, &logic)?;
+        let handle = self.handle.unwrap_or_default();
+
+        run_with(
+            &System(&self.clock),
+            stream,
+            producer,
+            inbox,
+            election,
+            logic,
+            &self.options,
+            &self.observer,
+            &handle,
+        )
+        .map_err(RunError::Diverged)
+    }
 }
 
 /// Runs the sequencer loop.
@@ -117,12 +948,22 @@ impl<S: Sequencer> Logic for Wrapper<'_, S> {
 /// main thread handles stream consumption and command processing. If the
 /// sequencer fails to renew its leadership lease, it terminates immediately
 /// to prevent split-brain scenarios.
+///
+/// `interval` paces every task of the election loop, and `wait_for` is the
+/// idle window after which the sequencer considers itself caught up.
+///
+/// Panics if either is zero, or, once stopped, if the stream diverges from
+/// the logic's state.
+#[deprecated(
+    since = "0.1.5",
+    note = "use `SequencerBuilder`, which names each option"
+)]
 pub fn run<S, P, I, E, L>(
     stream: &S,
     producer: &P,
     inbox: &I,
     election: &E,
-    mut logic: L,
+    logic: L,
     interval: Duration,
     wait_for: Duration,
 ) where
@@ -132,75 +973,415 @@ pub fn run<S, P, I, E, L>(
     E: Election,
     L: Sequencer,
 {
-    let status = AtomicUsize::new(STATUS_STARTING);
+    uniform(interval, wait_for)
+        .run(stream, producer, inbox, election, logic)
+        .unwrap_or_else(|e| panic!("{e}"));
+}
+
+/// Runs the sequencer loop, measuring the catch-up window and pacing the
+/// election loop with `clock`.
+///
+/// Panics like [`run`].
+#[deprecated(
+    since = "0.1.5",
+    note = "use `SequencerBuilder` with `SequencerBuilder::clock`"
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn run_with_clock<C, S, P, I, E, L>(
+    clock: &C,
+    stream: &S,
+    producer: &P,
+    inbox: &I,
+    election: &E,
+    logic: L,
+    interval: Duration,
+    wait_for: Duration,
+) where
+    C: Clock,
+    S: Stream,
+    P: Producer,
+    I: Inbox,
+    E: Election,
+    L: Sequencer,
+{
+    uniform(interval, wait_for)
+        .clock(clock)
+        .run(stream, producer, inbox, election, logic)
+        .unwrap_or_else(|e| panic!("{e}"));
+}
+
+/// Runs the sequencer loop, reporting its progress to `observer`.
+///
+/// Panics like [`run`].
+#[deprecated(
+    since = "0.1.5",
+    note = "use `SequencerBuilder` with `SequencerBuilder::observer`"
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn run_observed<S, P, I, E, L, O>(
+    stream: &S,
+    producer: &P,
+    inbox: &I,
+    election: &E,
+    logic: L,
+    interval: Duration,
+    wait_for: Duration,
+    observer: &O,
+) where
+    S: Stream,
+    P: Producer,
+    I: Inbox,
+    E: Election,
+    L: Sequencer,
+    O: Observer,
+{
+    uniform(interval, wait_for)
+        .observer(observer)
+        .run(stream, producer, inbox, election, logic)
+        .unwrap_or_else(|e| panic!("{e}"));
+}
+
+/// Runs the sequencer loop under the control of `handle`, returning the
+/// logic once the handle stops it.
+///
+/// Panics like [`run`].
+#[deprecated(
+    since = "0.1.5",
+    note = "use `SequencerBuilder` with `SequencerBuilder::handle`"
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn run_controlled<S, P, I, E, L>(
+    stream: &S,
+    producer: &P,
+    inbox: &I,
+    election: &E,
+    logic: L,
+    interval: Duration,
+    wait_for: Duration,
+    handle: &SequencerHandle,
+) -> L
+where
+    S: Stream,
+    P: Producer,
+    I: Inbox,
+    E: Election,
+    L: Sequencer,
+{
+    uniform(interval, wait_for)
+        .handle(handle.clone())
+        .run(stream, producer, inbox, election, logic)
+        .unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// The builder behind the positional runners.
+fn uniform(interval: Duration, wait_for: Duration) -> SequencerBuilder {
+    SequencerBuilder {
+        options: Options::uniform(interval, wait_for),
+        ..SequencerBuilder::new()
+    }
+}
+
+/// A recurring task of the election loop.
+struct Ticker {
+    interval: Duration,
+    next: Duration,
+}
+
+impl Ticker {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Duration::ZERO,
+        }
+    }
+
+    /// Returns `true` if the task is due at `now`, scheduling the next run.
+    fn due(&mut self, now: Duration) -> bool {
+        if now < self.next {
+            return false;
+        }
+        self.next = now + self.interval;
+        true
+    }
+}
+
+/// Runs the sequencer loop within the given environment, returning the
+/// logic once `handle` stops it.
+///
+/// A divergence found while consuming the stream stops the run like the
+/// handle would, and is returned once the election loop has finished.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run_with<V, S, P, I, E, L, O>(
+    env: &V,
+    stream: &S,
+    producer: &P,
+    inbox: &I,
+    election: &E,
+    mut logic: L,
+    options: &Options,
+    observer: &O,
+    handle: &SequencerHandle,
+) -> Result<L, Divergence>
+where
+    V: Env,
+    S: Stream,
+    P: Producer,
+    I: Inbox,
+    E: Election,
+    L: Sequencer,
+    O: Observer,
+{
+    let control = &*handle.inner;
+    let phase = Phase {
+        status: &control.status,
+        observer,
+    };
     let last_step = AtomicU64::new(0);
+    let mut diverged = None;
     let activate = logic.activator();
     let heartbeat = logic.heartbeat();
+    let stream_heartbeat = logic.stream_heartbeat();
+
+    // Local deadline of the lease in clock nanoseconds, counted from the
+    // start of the last successful call, since the backend grants the lease
+    // some time after it is requested.
+    let lease = election.lease();
+    let lease_expiry = AtomicU64::new(0);
+    let extend = |start: Duration| {
+        if let Some(lease) = lease {
+            lease_expiry.store((start + lease).as_nanos() as u64, Ordering::Relaxed);
+        }
+    };
 
+    let lose = || match options.lost_lease {
+        LostLease::Exit => env.exit(),
+        LostLease::Stop => control.stop(),
+    };
+
+    // Publishes the events and returns `true`, unless the lease may have
+    // lapsed, which is handled as losing it.
+    let publish = |events: &[Vec<u8>]| {
+        let start = env.now();
+        if lease.is_some()
+            && start + options.lease_margin
+                >= Duration::from_nanos(lease_expiry.load(Ordering::Relaxed))
+        {
+            lose();
+            return false;
+        }
+
+        match events {
+            [event] => producer.publish(event),
+            events => producer.publish_batch(events),
+        }
+        let latency = env.now().saturating_sub(start);
+        for event in events {
+            observer.publish(event, latency);
+        }
+        true
+    };
+
+    let renew = || {
+        let start = env.now();
+        let renewed = election.renew();
+        observer.renew(renewed);
+        if renewed {
+            extend(start);
+        }
+        renewed
+    };
+
+    let beat = |timestamp: Duration| {
+        control
+            .heartbeat
+            .store(timestamp.as_nanos() as u64, Ordering::Relaxed);
+    };
+
+    let ticket = env.fork();
     thread::scope(|s| {
         s.spawn(|| {
-            loop {
-                match status.load(Ordering::Relaxed) {
-                    // Phase 1: Consume stream to rebuild state. Clear inbox since
-                    // commands received before leadership should be discarded.
-                    STATUS_STARTING => {
-                        if (last_step.load(Ordering::Relaxed) + wait_for.as_nanos() as u64) < now()
-                        {
-                            status.store(STATUS_CAUGHT_UP, Ordering::Relaxed);
+            env.enter(ticket, &mut || {
+                let mut renewal = Ticker::new(options.renew_interval);
+                let mut activation = Ticker::new(options.activation_interval);
+                let mut heartbeats = Ticker::new(options.heartbeat_interval);
+
+                while !control.stopped() {
+                    let now = env.now();
+                    let next = match phase.load() {
+                        // Phase 1: Consume stream to rebuild state. Clear inbox since
+                        // commands received before leadership should be discarded.
+                        STATUS_STARTING => {
+                            if let Some(window) = options.catch_up.idle()
+                                && (last_step.load(Ordering::Relaxed) + window.as_nanos() as u64)
+                                    < now.as_nanos() as u64
+                            {
+                                phase.advance(STATUS_STARTING, STATUS_CAUGHT_UP);
+                            }
+                            now + options.election_interval
                         }
-                    }
 
-                    // Phase 2: Caught up with stream. Attempt to acquire leadership.
-                    STATUS_CAUGHT_UP => {
-                        if election.elect() {
-                            status.store(STATUS_LEADER, Ordering::Relaxed);
+                        // Phase 2: Caught up with stream. Attempt to acquire leadership.
+                        STATUS_CAUGHT_UP => {
+                            let won = election.elect();
+                            observer.elect(won);
+                            if won {
+                                extend(now);
+                                phase.advance(STATUS_CAUGHT_UP, STATUS_LEADER);
+                            }
+                            now + options.election_interval
                         }
-                    }
 
-                    // Phase 3: Won election. Repeatedly publish activation until it
-                    // lands at the stream tip, ensuring no events are overwritten.
-                    STATUS_LEADER => {
-                        if !election.renew() {
-                            process::exit(1);
+                        // Phase 3: Won election. Repeatedly publish activation until it
+                        // lands at the stream tip, ensuring no events are overwritten.
+                        STATUS_LEADER => {
+                            if renewal.due(now) && !renew() {
+                                lose();
+                                continue;
+                            }
+                            if activation.due(now) && !publish(&[activate()]) {
+                                continue;
+                            }
+                            renewal.next.min(activation.next)
                         }
-                        producer.publish(&activate());
-                    }
 
-                    // Phase 4: Activation observed. Continue renewing lease.
-                    STATUS_ACTIVATED => {
-                        if !election.renew() {
-                            process::exit(1);
+                        // Phase 4: Activation observed. Continue renewing lease and
+                        // publishing heartbeats.
+                        STATUS_ACTIVATED => {
+                            if renewal.due(now) {
+                                // A wedged command loop must not keep the lease.
+                                let renewed = if control.stalled(now, options.progress_deadline) {
+                                    observer.renew(false);
+                                    false
+                                } else {
+                                    renew()
+                                };
+                                if !renewed {
+                                    lose();
+                                    continue;
+                                }
+                                inbox.send(&heartbeat());
+                                if stream_heartbeat.is_none() {
+                                    beat(env.timestamp());
+                                }
+                            }
+
+                            match &stream_heartbeat {
+                                Some(stream_heartbeat) => {
+                                    if heartbeats.due(now) {
+                                        let timestamp = env.timestamp();
+                                        if !publish(&[stream_heartbeat(timestamp)]) {
+                                            continue;
+                                        }
+                                        control.applied(1);
+                                        beat(timestamp);
+                                    }
+                                    renewal.next.min(heartbeats.next)
+                                }
+                                None => renewal.next,
+                            }
                         }
-                        inbox.send(&heartbeat());
-                    }
 
-                    _ => unreachable!(),
+                        _ => unreachable!(),
+                    };
+                    env.sleep(next.saturating_sub(env.now()));
                 }
-                thread::sleep(interval);
-            }
+
+                // Wake the command loop so it notices the stop.
+                if phase.load() == STATUS_ACTIVATED {
+                    inbox.send(&heartbeat());
+                }
+            });
         });
 
         // Consume stream until activation event is observed
         let mut wrapper = Wrapper {
-            status: &status,
+            env,
+            phase: &phase,
+            control,
+            catch_up: options.catch_up,
             last_step: &last_step,
             logic: &mut logic,
         };
-        consumer::run(stream, &mut wrapper);
+        consumer::run_checked(stream, &mut wrapper, |divergence| {
+            diverged.get_or_insert(divergence);
+            control.stop();
+        });
 
         // Phase 4 (continued): Process commands from inbox
-        loop {
-            let command = inbox.recv();
-            if let Some(event) = wrapper.logic.process(&command) {
-                producer.publish(&event);
+        let mut commands = Vec::with_capacity(options.batch);
+        let mut events = Vec::new();
+        'commands: loop {
+            control.wait_resumed();
+            if control.stopped() {
+                break;
+            }
+            control.check_in(env.now());
+
+            // Take a batch of commands, blocking only for the first.
+            commands.clear();
+            loop {
+                let command = inbox.recv();
+                if control.stopped() {
+                    break 'commands;
+                }
+                // Receiving a command, heartbeats included, is progress
+                // whether or not `process` turns it into an event.
+                let received = env.now();
+                control.check_in(received);
+                let depth = inbox.depth();
+                if let Some(depth) = depth {
+                    observer.inbox_depth(depth);
+                }
+                commands.push((command, received));
+
+                if commands.len() >= options.batch || depth.is_none_or(|depth| depth == 0) {
+                    break;
+                }
+            }
+
+            events.clear();
+            let mut produced = Vec::with_capacity(commands.len());
+            for (command, received) in &commands {
+                let event = wrapper.logic.process(command);
+                observer.process(
+                    command,
+                    event.as_deref(),
+                    env.now().saturating_sub(*received),
+                );
+
+                if let Some(event) = event {
+                    produced.push((events.len(), command, received));
+                    events.push(event);
+                    if let Some(checksum) = wrapper.logic.checksum() {
+                        events.push(checksum);
+                    }
+                }
+            }
+
+            if !events.is_empty() {
+                if !publish(&events) {
+                    break;
+                }
+                control.applied(events.len() as u64);
+                for (i, command, received) in produced {
+                    observer.committed(command, &events[i], env.now().saturating_sub(*received));
+                }
+            }
+
+            if control.snapshot.swap(false, Ordering::Relaxed) {
+                wrapper.logic.save_snapshot();
             }
         }
     });
-}
 
-fn now() -> u64 {
-    SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_nanos() as u64
+    // Hand leadership over rather than leave it to expire.
+    if matches!(phase.load(), STATUS_LEADER | STATUS_ACTIVATED) {
+        election.resign();
+    }
+    phase.set(STATUS_STOPPED);
+
+    match diverged {
+        Some(divergence) => Err(divergence),
+        None => Ok(logic),
+    }
 }
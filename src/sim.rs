@@ -0,0 +1,845 @@
+//! Deterministic simulation of sequencers, consumers and their backends.
+//!
+//! Enabled with the `sim` feature. A [`Sim`] runs any number of tasks—each a
+//! real thread—under a scheduler that lets exactly one of them run at a time.
+//! Whenever the running task blocks on a simulated backend or sleeps, the
+//! scheduler picks the next task using a seeded random number generator, and
+//! advances a virtual clock only when every task is blocked. Given the same
+//! seed and the same task code, a simulation therefore replays identically,
+//! provided the tasks themselves avoid other sources of nondeterminism such as
+//! wall-clock reads or `HashMap` iteration order.
+//!
+//! Tasks belong to nodes, which model processes: a node may be partitioned
+//! from the stream and election backends, or killed, which unwinds all of its
+//! tasks. Sequencers started with [`Sim::sequencer`] are killed in place of
+//! exiting the process when they lose their lease.
+//!
+//! ```ignore
+//! let mut sim = Sim::new(42);
+//! let stream = sim.stream();
+//! let election = sim.election(Duration::from_secs(1));
+//!
+//! for name in ["a", "b"] {
+//!     let builder = SequencerBuilder::new().catch_up(CatchUp::Idle(Duration::from_millis(500)));
+//!     sim.sequencer(name, builder, stream.clone(), stream.producer(), sim.inbox(),
+//!         election.clone(), MyLogic::new());
+//! }
+//!
+//! sim.run_for(Duration::from_secs(10));
+//! stream.assert_exclusive();
+//! ```
+
+use crate::{
+    Receiver,
+    clock::Clock,
+    election::{Election, Leader},
+    inbox::{Inbox, Sender},
+    observer::Observer,
+    rng::Rng,
+    sequencer::{self, Env, Sequencer, SequencerBuilder},
+    stream::{Producer, Stream},
+};
+
+use std::{
+    any::Any,
+    cell::Cell,
+    collections::VecDeque,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+/// Randomized faults injected by the simulation.
+///
+/// All probabilities are in `[0, 1]` and default to zero.
+#[derive(Clone, Debug, Default)]
+pub struct Faults {
+    /// Probability that a publish stalls in flight. A stalled write still
+    /// lands, even if its publisher dies in the meantime.
+    pub publish_stall: f64,
+    /// Maximum duration of a publish stall.
+    pub max_publish_stall: Duration,
+    /// Probability, on each election call, that the calling node becomes
+    /// partitioned from the stream and election backends.
+    pub partition: f64,
+    /// Duration of a randomly injected partition.
+    pub partition_duration: Duration,
+    /// Probability that a renewal is delayed by up to the lease duration
+    /// before reaching the election backend, so that the lease may expire
+    /// and pass to another node before the holder learns it was lost.
+    pub lease_expiry: f64,
+}
+
+/// Payload used to unwind the tasks of a killed node.
+struct Killed;
+
+thread_local! {
+    static TASK: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+#[derive(Clone, Copy)]
+enum Wait {
+    /// Runnable immediately; used to yield.
+    Ready,
+    /// Runnable once the clock reaches the given time.
+    Until(u64),
+    /// Runnable once the log holds more than the given number of events and
+    /// the task's node is not partitioned.
+    Log(usize, u64),
+    /// Runnable once the inbox is non-empty.
+    Inbox(usize),
+    /// Runnable once the task's node is not partitioned.
+    Healed,
+}
+
+struct Task {
+    node: usize,
+    wait: Wait,
+    done: bool,
+}
+
+struct Node {
+    name: String,
+    dead: bool,
+    partitioned_until: u64,
+}
+
+struct Lease {
+    holder: Option<usize>,
+    expiry: u64,
+}
+
+/// A delayed write to a log.
+struct Write {
+    at: u64,
+    log: usize,
+    node: usize,
+    data: Vec<u8>,
+}
+
+struct State {
+    now: u64,
+    end: u64,
+    rng: Rng,
+    faults: Faults,
+    tasks: Vec<Task>,
+    nodes: Vec<Node>,
+    /// The task currently allowed to run, or `None` while paused.
+    running: Option<usize>,
+    panic: Option<Box<dyn Any + Send>>,
+    logs: Vec<Vec<(usize, Vec<u8>)>>,
+    inboxes: Vec<VecDeque<Vec<u8>>>,
+    leases: Vec<Lease>,
+    in_flight: Vec<Write>,
+}
+
+impl State {
+    fn partitioned(&self, node: usize) -> bool {
+        self.nodes[node].partitioned_until > self.now
+    }
+
+    fn runnable(&self, id: usize) -> bool {
+        let task = &self.tasks[id];
+        if task.done {
+            return false;
+        }
+        if self.nodes[task.node].dead {
+            return true;
+        }
+
+        match task.wait {
+            Wait::Ready => true,
+            Wait::Until(time) => time <= self.now,
+            Wait::Log(log, offset) => {
+                self.logs[log].len() as u64 > offset && !self.partitioned(task.node)
+            }
+            Wait::Inbox(inbox) => !self.inboxes[inbox].is_empty(),
+            Wait::Healed => !self.partitioned(task.node),
+        }
+    }
+
+    /// Appends in-flight writes that are due, in the order they were issued.
+    fn land(&mut self) {
+        let now = self.now;
+        let (due, pending) = mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|write| write.at <= now);
+        self.in_flight = pending;
+
+        for write in due {
+            self.logs[write.log].push((write.node, write.data));
+        }
+    }
+
+    /// Returns the earliest future time at which a blocked task may become
+    /// runnable or an in-flight write lands.
+    fn next_wakeup(&self) -> Option<u64> {
+        let landing = self.in_flight.iter().map(|write| write.at);
+        self.tasks
+            .iter()
+            .filter(|task| !task.done)
+            .filter_map(|task| match task.wait {
+                Wait::Until(time) => Some(time),
+                Wait::Log(..) | Wait::Healed if self.partitioned(task.node) => {
+                    Some(self.nodes[task.node].partitioned_until)
+                }
+                _ => None,
+            })
+            .chain(landing)
+            .min()
+    }
+}
+
+struct World {
+    state: Mutex<State>,
+    turn: Condvar,
+}
+
+impl World {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Hands the baton to a randomly chosen runnable task, advancing the clock
+    /// as needed. Pauses the simulation when the end time is reached or no
+    /// task can make progress.
+    fn schedule(&self, state: &mut State) {
+        loop {
+            state.land();
+
+            let ready: Vec<usize> = (0..state.tasks.len())
+                .filter(|&id| state.runnable(id))
+                .collect();
+
+            if !ready.is_empty() {
+                let pick = ready[state.rng.below(ready.len() as u64) as usize];
+                state.running = Some(pick);
+                break;
+            }
+
+            match state.next_wakeup() {
+                Some(time) if time <= state.end => state.now = time,
+                _ => {
+                    state.now = state.now.max(state.end);
+                    state.running = None;
+                    break;
+                }
+            }
+        }
+        self.turn.notify_all();
+    }
+
+    /// Waits until `id` holds the baton.
+    fn wait_turn<'a>(
+        &'a self,
+        mut state: MutexGuard<'a, State>,
+        id: usize,
+    ) -> MutexGuard<'a, State> {
+        while state.running != Some(id) {
+            state = self.turn.wait(state).unwrap();
+        }
+        state
+    }
+
+    /// Returns the calling task, unwinding immediately if it has already
+    /// finished because its node was killed.
+    fn current(&self) -> usize {
+        let id = TASK.get().expect("not called from a simulation task");
+        if self.lock().tasks[id].done {
+            panic::resume_unwind(Box::new(Killed));
+        }
+        id
+    }
+
+    /// Blocks the calling task until `wait` is satisfied and it is scheduled
+    /// again. Unwinds the task if its node was killed in the meantime.
+    fn block(&self, wait: Wait) -> (MutexGuard<'_, State>, usize) {
+        let id = self.current();
+        let mut state = self.lock();
+
+        state.tasks[id].wait = wait;
+        self.schedule(&mut state);
+        let mut state = self.wait_turn(state, id);
+        state.tasks[id].wait = Wait::Ready;
+
+        if state.nodes[state.tasks[id].node].dead {
+            self.finish(&mut state, id);
+            drop(state);
+            panic::resume_unwind(Box::new(Killed));
+        }
+        (state, id)
+    }
+
+    fn finish(&self, state: &mut State, id: usize) {
+        state.tasks[id].done = true;
+        self.schedule(state);
+    }
+
+    fn add_task(&self, state: &mut State, node: usize) -> usize {
+        state.tasks.push(Task {
+            node,
+            wait: Wait::Ready,
+            done: false,
+        });
+        state.tasks.len() - 1
+    }
+
+    /// Runs `f` as task `id` on the calling thread.
+    fn enter(&self, id: usize, f: &mut dyn FnMut()) {
+        TASK.set(Some(id));
+
+        let state = self.wait_turn(self.lock(), id);
+        let dead = state.nodes[state.tasks[id].node].dead;
+        drop(state);
+
+        let result = match dead {
+            true => Err(Box::new(Killed) as Box<dyn Any + Send>),
+            false => panic::catch_unwind(AssertUnwindSafe(f)),
+        };
+
+        let mut state = self.lock();
+        if !state.tasks[id].done {
+            if let Err(payload) = result
+                && !payload.is::<Killed>()
+            {
+                state.panic.get_or_insert(payload);
+            }
+            self.finish(&mut state, id);
+        }
+    }
+
+    fn kill(&self, state: &mut State, node: usize) {
+        state.nodes[node].dead = true;
+    }
+}
+
+/// A deterministic simulation.
+///
+/// Dropping the simulation kills every node and waits for all tasks to
+/// finish.
+pub struct Sim {
+    world: Arc<World>,
+}
+
+impl Sim {
+    /// Creates a simulation whose scheduling decisions and faults are driven
+    /// by `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self::with_faults(seed, Faults::default())
+    }
+
+    /// Creates a simulation injecting the given randomized faults.
+    pub fn with_faults(seed: u64, faults: Faults) -> Self {
+        Self {
+            world: Arc::new(World {
+                state: Mutex::new(State {
+                    now: 0,
+                    end: 0,
+                    rng: Rng::new(seed),
+                    faults,
+                    tasks: Vec::new(),
+                    nodes: Vec::new(),
+                    running: None,
+                    panic: None,
+                    logs: Vec::new(),
+                    inboxes: Vec::new(),
+                    leases: Vec::new(),
+                    in_flight: Vec::new(),
+                }),
+                turn: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Returns the current virtual time.
+    pub fn now(&self) -> Duration {
+        Duration::from_nanos(self.world.lock().now)
+    }
+
+    /// Returns a handle to the virtual clock.
+    pub fn clock(&self) -> SimClock {
+        SimClock {
+            world: Arc::clone(&self.world),
+        }
+    }
+
+    /// Creates an empty stream.
+    pub fn stream(&self) -> SimStream {
+        let mut state = self.world.lock();
+        state.logs.push(Vec::new());
+
+        SimStream {
+            world: Arc::clone(&self.world),
+            log: state.logs.len() - 1,
+        }
+    }
+
+    /// Creates an empty inbox.
+    pub fn inbox(&self) -> SimInbox {
+        let mut state = self.world.lock();
+        state.inboxes.push(VecDeque::new());
+
+        SimInbox {
+            world: Arc::clone(&self.world),
+            inbox: state.inboxes.len() - 1,
+        }
+    }
+
+    /// Creates an election whose leases last `lease` in virtual time.
+    pub fn election(&self, lease: Duration) -> SimElection {
+        let mut state = self.world.lock();
+        state.leases.push(Lease {
+            holder: None,
+            expiry: 0,
+        });
+
+        SimElection {
+            world: Arc::clone(&self.world),
+            lease: state.leases.len() - 1,
+            duration: lease.as_nanos() as u64,
+        }
+    }
+
+    /// Starts a new node named `name` running `f` as its first task.
+    ///
+    /// Each call creates a distinct node, so spawning with the name of a
+    /// killed node models a process restart.
+    pub fn spawn<F>(&self, name: &str, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let id = {
+            let mut state = self.world.lock();
+            state.nodes.push(Node {
+                name: name.to_owned(),
+                dead: false,
+                partitioned_until: 0,
+            });
+            let node = state.nodes.len() - 1;
+            self.world.add_task(&mut state, node)
+        };
+
+        let world = Arc::clone(&self.world);
+        let mut f = Some(f);
+        thread::spawn(move || world.enter(id, &mut || f.take().unwrap()()));
+    }
+
+    /// Starts a new node named `name` running a sequencer configured by
+    /// `builder` against the given backends, as
+    /// [`SequencerBuilder::run`] would.
+    ///
+    /// The sequencer observes the virtual clock in place of the builder's,
+    /// and a lost lease under [`LostLease::Exit`] kills the node instead of
+    /// exiting the process. Panics if an option is invalid; a divergence
+    /// panics the task, which [`run_for`](Sim::run_for) propagates.
+    ///
+    /// [`LostLease::Exit`]: crate::sequencer::LostLease::Exit
+    #[allow(clippy::too_many_arguments)]
+    pub fn sequencer<C, O, S, P, I, E, L>(
+        &self,
+        name: &str,
+        builder: SequencerBuilder<C, O>,
+        stream: S,
+        producer: P,
+        inbox: I,
+        election: E,
+        logic: L,
+    ) where
+        C: Clock,
+        O: Observer + Send + 'static,
+        S: Stream + Send + 'static,
+        P: Producer + Send + 'static,
+        I: Inbox + Send + 'static,
+        E: Election + Send + 'static,
+        L: Sequencer + Send + 'static,
+    {
+        if let Err(invalid) = builder.check(&election, &logic) {
+            panic!("{invalid}");
+        }
+        let (options, observer, handle) = builder.into_parts();
+
+        let env = self.clock();
+        self.spawn(name, move || {
+            if let Err(divergence) = sequencer::run_with(
+                &env, &stream, &producer, &inbox, &election, logic, &options, &observer, &handle,
+            ) {
+                panic!("{divergence}");
+            }
+        });
+    }
+
+    /// Runs the simulation until `duration` of virtual time has passed or no
+    /// task can make progress.
+    ///
+    /// If a task panicked, the panic is propagated to the caller.
+    pub fn run_for(&mut self, duration: Duration) {
+        let mut state = self.world.lock();
+        state.end = state.now + duration.as_nanos() as u64;
+        self.world.schedule(&mut state);
+
+        while state.running.is_some() {
+            state = self.world.turn.wait(state).unwrap();
+        }
+
+        if let Some(payload) = state.panic.take() {
+            drop(state);
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Partitions every live node named `name` from the stream and election
+    /// backends for `duration`.
+    pub fn partition(&self, name: &str, duration: Duration) {
+        let mut state = self.world.lock();
+        let until = state.now + duration.as_nanos() as u64;
+        for node in state.nodes.iter_mut().filter(|node| node.name == name) {
+            node.partitioned_until = until;
+        }
+    }
+
+    /// Kills every live node named `name`, unwinding its tasks.
+    ///
+    /// Leases held by the node are left to expire.
+    pub fn kill(&self, name: &str) {
+        let mut state = self.world.lock();
+        for node in 0..state.nodes.len() {
+            if state.nodes[node].name == name {
+                self.world.kill(&mut state, node);
+            }
+        }
+    }
+}
+
+impl Drop for Sim {
+    fn drop(&mut self) {
+        let mut state = self.world.lock();
+        for node in 0..state.nodes.len() {
+            self.world.kill(&mut state, node);
+        }
+
+        state.end = u64::MAX;
+        self.world.schedule(&mut state);
+        while state.running.is_some() {
+            state = self.world.turn.wait(state).unwrap();
+        }
+    }
+}
+
+/// The virtual clock, usable from within tasks.
+///
+/// Tasks that loop without touching a simulated backend must
+/// [`sleep`](Clock::sleep), otherwise virtual time never advances.
+#[derive(Clone)]
+pub struct SimClock {
+    world: Arc<World>,
+}
+
+impl Clock for SimClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.world.lock().now)
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let now = self.world.lock().now;
+        drop(
+            self.world
+                .block(Wait::Until(now + duration.as_nanos() as u64)),
+        );
+    }
+}
+
+impl Env for SimClock {
+    fn timestamp(&self) -> Duration {
+        self.now()
+    }
+
+    fn exit(&self) -> ! {
+        let id = self.world.current();
+        let mut state = self.world.lock();
+        let node = state.tasks[id].node;
+        self.world.kill(&mut state, node);
+        self.world.finish(&mut state, id);
+        drop(state);
+
+        panic::resume_unwind(Box::new(Killed))
+    }
+
+    fn fork(&self) -> usize {
+        let id = self.world.current();
+        let mut state = self.world.lock();
+        let node = state.tasks[id].node;
+        self.world.add_task(&mut state, node)
+    }
+
+    fn enter(&self, ticket: usize, f: &mut dyn FnMut()) {
+        self.world.enter(ticket, f);
+    }
+}
+
+/// An in-memory stream that records which node published each event.
+#[derive(Clone)]
+pub struct SimStream {
+    world: Arc<World>,
+    log: usize,
+}
+
+impl SimStream {
+    /// Creates a producer that appends to this stream.
+    pub fn producer(&self) -> SimProducer {
+        SimProducer {
+            world: Arc::clone(&self.world),
+            log: self.log,
+        }
+    }
+
+    /// Returns every event published so far.
+    pub fn events(&self) -> Vec<Vec<u8>> {
+        let state = self.world.lock();
+        state.logs[self.log]
+            .iter()
+            .map(|(_, data)| data.clone())
+            .collect()
+    }
+
+    /// Panics if the publishes of two nodes are interleaved.
+    ///
+    /// Once another node has published to the stream, an earlier publisher
+    /// must never publish again. A restarted process is a new node, so it may
+    /// publish again after being replaced.
+    pub fn assert_exclusive(&self) {
+        if let Some(violation) = self.violation() {
+            panic!("{violation}");
+        }
+    }
+
+    fn violation(&self) -> Option<String> {
+        let state = self.world.lock();
+        let name = |node: usize| format!("{}#{}", state.nodes[node].name, node);
+        let mut retired = vec![false; state.nodes.len()];
+        let mut last = None;
+
+        for (offset, &(node, _)) in state.logs[self.log].iter().enumerate() {
+            if last == Some(node) {
+                continue;
+            }
+            if let Some(previous) = last {
+                if retired[node] {
+                    return Some(format!(
+                        "{} published at offset {} after being replaced by {}",
+                        name(node),
+                        offset,
+                        name(previous),
+                    ));
+                }
+                retired[previous] = true;
+            }
+            last = Some(node);
+        }
+        None
+    }
+}
+
+impl Stream for SimStream {
+    type Receiver = SimReceiver;
+
+    fn subscribe(&self, offset: u64) -> Self::Receiver {
+        SimReceiver {
+            world: Arc::clone(&self.world),
+            log: self.log,
+            next: Mutex::new(offset),
+        }
+    }
+}
+
+/// Receiver over a [`SimStream`].
+///
+/// Delivers nothing while the receiving node is partitioned.
+pub struct SimReceiver {
+    world: Arc<World>,
+    log: usize,
+    next: Mutex<u64>,
+}
+
+impl Receiver for SimReceiver {
+    fn recv(&self) -> Vec<u8> {
+        let mut next = self.next.lock().unwrap();
+        let (state, _) = self.world.block(Wait::Log(self.log, *next));
+        let data = state.logs[self.log][*next as usize].1.clone();
+        *next += 1;
+
+        data
+    }
+}
+
+/// Producer appending to a [`SimStream`] on behalf of the calling node.
+///
+/// Publishes block while the node is partitioned, and may stall according
+/// to the simulation's [`Faults`].
+pub struct SimProducer {
+    world: Arc<World>,
+    log: usize,
+}
+
+impl Producer for SimProducer {
+    fn publish(&self, data: &[u8]) {
+        let (mut state, id) = self.world.block(Wait::Healed);
+        let node = state.tasks[id].node;
+
+        let faults = state.faults.clone();
+        if !state.rng.chance(faults.publish_stall) {
+            state.logs[self.log].push((node, data.to_vec()));
+            return;
+        }
+
+        // The write is in flight: it lands after the stall even if the
+        // publisher dies in the meantime.
+        let at = state.now + state.rng.jitter(faults.max_publish_stall).as_nanos() as u64;
+        state.in_flight.push(Write {
+            at,
+            log: self.log,
+            node,
+            data: data.to_vec(),
+        });
+        drop(state);
+        drop(self.world.block(Wait::Until(at)));
+    }
+}
+
+/// An in-memory inbox.
+#[derive(Clone)]
+pub struct SimInbox {
+    world: Arc<World>,
+    inbox: usize,
+}
+
+impl Receiver for SimInbox {
+    fn recv(&self) -> Vec<u8> {
+        let (mut state, _) = self.world.block(Wait::Inbox(self.inbox));
+        state.inboxes[self.inbox].pop_front().unwrap()
+    }
+}
+
+impl Sender for SimInbox {
+    fn send(&self, command: &[u8]) {
+        let (mut state, _) = self.world.block(Wait::Ready);
+        state.inboxes[self.inbox].push_back(command.to_vec());
+    }
+}
+
+impl Inbox for SimInbox {
+    fn clear(&self) {
+        self.world.lock().inboxes[self.inbox].clear();
+    }
+
+    fn depth(&self) -> Option<usize> {
+        Some(self.world.lock().inboxes[self.inbox].len())
+    }
+}
+
+/// A lease-based election measured in virtual time.
+///
+/// Partitioned nodes can neither acquire nor renew the lease.
+#[derive(Clone)]
+pub struct SimElection {
+    world: Arc<World>,
+    lease: usize,
+    duration: u64,
+}
+
+impl SimElection {
+    /// Expires the current lease without notifying its holder.
+    pub fn expire(&self) {
+        self.world.lock().leases[self.lease].holder = None;
+    }
+
+    /// Returns the name of the node holding an unexpired lease, if any.
+    pub fn holder(&self) -> Option<String> {
+        let state = self.world.lock();
+        let lease = &state.leases[self.lease];
+        lease
+            .holder
+            .filter(|_| lease.expiry > state.now)
+            .map(|node| state.nodes[node].name.clone())
+    }
+
+    /// Yields, applies randomized partitions, and returns the calling node
+    /// if it can reach the election backend.
+    fn call(&self) -> Option<(MutexGuard<'_, State>, usize)> {
+        let (mut state, id) = self.world.block(Wait::Ready);
+        let node = state.tasks[id].node;
+
+        let faults = state.faults.clone();
+        if state.rng.chance(faults.partition) {
+            state.nodes[node].partitioned_until =
+                state.now + faults.partition_duration.as_nanos() as u64;
+        }
+
+        match state.partitioned(node) {
+            true => None,
+            false => Some((state, node)),
+        }
+    }
+}
+
+impl Election for SimElection {
+    fn elect(&self) -> bool {
+        let Some((mut state, node)) = self.call() else {
+            return false;
+        };
+
+        let now = state.now;
+        let lease = &state.leases[self.lease];
+        if lease.holder.is_some_and(|holder| holder != node) && lease.expiry > now {
+            return false;
+        }
+
+        let lease = &mut state.leases[self.lease];
+        lease.holder = Some(node);
+        lease.expiry = now + self.duration;
+        true
+    }
+
+    fn renew(&self) -> bool {
+        let Some((mut state, node)) = self.call() else {
+            return false;
+        };
+
+        let lease_expiry = state.faults.lease_expiry;
+        if state.rng.chance(lease_expiry) {
+            let delay = state.rng.jitter(Duration::from_nanos(self.duration));
+            let at = state.now + delay.as_nanos() as u64;
+            drop(state);
+            state = self.world.block(Wait::Until(at)).0;
+        }
+
+        let now = state.now;
+        let lease = &mut state.leases[self.lease];
+        if lease.holder != Some(node) || lease.expiry <= now {
+            return false;
+        }
+
+        lease.expiry = now + self.duration;
+        true
+    }
+
+    fn lease(&self) -> Option<Duration> {
+        Some(Duration::from_nanos(self.duration))
+    }
+
+    fn resign(&self) {
+        let Some((mut state, node)) = self.call() else {
+            return;
+        };
+
+        let lease = &mut state.leases[self.lease];
+        if lease.holder == Some(node) {
+            lease.holder = None;
+        }
+    }
+
+    fn leader(&self) -> Option<Leader> {
+        self.holder().map(|id| Leader { id, address: None })
+    }
+}
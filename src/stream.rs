@@ -36,5 +36,18 @@ pub trait Producer: Sync {
     /// If persistence becomes impossible, the implementation must panic or abort to 
     /// prevent the system from continuing without its durability guarantees.
     fn publish(&self, data: &[u8]);
+
+    /// Publishes several events to the stream, in order.
+    ///
+    /// Blocks until every event is persisted and durable, as [`publish`]
+    /// does for one. The default publishes them one at a time; backends
+    /// that can persist a batch in one round trip should override it.
+    ///
+    /// [`publish`]: Producer::publish
+    fn publish_batch(&self, events: &[Vec<u8>]) {
+        for event in events {
+            self.publish(event);
+        }
+    }
 }
 
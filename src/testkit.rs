@@ -0,0 +1,558 @@
+//! Given/when/then scenarios for [`Logic`] and [`Sequencer`] implementations.
+//!
+//! Enabled with the `testkit` feature. A [`Scenario`] feeds prior events to a
+//! logic instance, issues commands to it, and asserts on the events produced,
+//! the commands rejected, and the resulting state:
+//!
+//! ```ignore
+//! Scenario::new(Counter::new, |counter| counter.value)
+//!     .given([b"inc:1"])
+//!     .when(b"inc")
+//!     .then_event(b"inc:2")
+//!     .when(b"dec:5")
+//!     .then_rejected()
+//!     .then_state(2);
+//! ```
+//!
+//! After every command, the scenario replays the prior events and every
+//! event produced so far into a fresh instance and checks that it reaches
+//! the same state as the sequencer. This is the replayability promise: a
+//! consumer running the same logic over the stream must end up where the
+//! sequencer did.
+//!
+//! State is compared through a projection supplied to [`Scenario::new`],
+//! since neither trait exposes state directly.
+//!
+//! [`Equivalence`] checks the same promise over randomly generated command
+//! sequences, shrinking any failure to a minimal counterexample.
+
+use crate::{logic::Logic, rng::Rng, sequencer::Sequencer};
+
+use std::fmt::Debug;
+
+/// A scenario under test.
+///
+/// Every assertion panics with a description of the mismatch.
+pub struct Scenario<L, F, G> {
+    fresh: F,
+    project: G,
+    logic: L,
+    history: Vec<Vec<u8>>,
+    produced: Vec<Vec<u8>>,
+    last: Option<Option<Vec<u8>>>,
+}
+
+impl<L, F, G, T> Scenario<L, F, G>
+where
+    L: Logic,
+    F: Fn() -> L,
+    G: Fn(&L) -> T,
+    T: PartialEq + Debug,
+{
+    /// Creates a scenario around a logic instance from `fresh`, whose state is
+    /// compared through `project`.
+    ///
+    /// `fresh` is called again for every replay check, so it must produce
+    /// identical instances.
+    pub fn new(fresh: F, project: G) -> Self {
+        let mut logic = fresh();
+        logic.load();
+
+        Self {
+            fresh,
+            project,
+            logic,
+            history: Vec::new(),
+            produced: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// Feeds events that were already on the stream.
+    pub fn given<I, E>(&mut self, events: I) -> &mut Self
+    where
+        I: IntoIterator<Item = E>,
+        E: AsRef<[u8]>,
+    {
+        for event in events {
+            let event = event.as_ref();
+            self.logic.step(event);
+            self.history.push(event.to_vec());
+        }
+        self
+    }
+
+    /// Asserts that the projected state equals `expected`.
+    #[track_caller]
+    pub fn then_state(&mut self, expected: T) -> &mut Self {
+        let state = (self.project)(&self.logic);
+        assert!(
+            state == expected,
+            "expected state {expected:?}, found {state:?}"
+        );
+        self
+    }
+
+    /// Returns every event produced by commands so far.
+    pub fn produced(&self) -> &[Vec<u8>] {
+        &self.produced
+    }
+
+    /// Returns the logic under test.
+    pub fn logic(&self) -> &L {
+        &self.logic
+    }
+
+    /// Asserts that replaying the stream into a fresh instance reaches the
+    /// same state as the logic under test.
+    #[track_caller]
+    pub fn then_replays(&mut self) -> &mut Self {
+        let mut replica = (self.fresh)();
+        replica.load();
+        for event in &self.history {
+            replica.step(event);
+        }
+
+        let expected = (self.project)(&self.logic);
+        let replayed = (self.project)(&replica);
+        assert!(
+            replayed == expected,
+            "replaying {} events reached {replayed:?}, but the sequencer holds {expected:?}",
+            self.history.len(),
+        );
+        self
+    }
+}
+
+impl<L, F, G, T> Scenario<L, F, G>
+where
+    L: Sequencer,
+    F: Fn() -> L,
+    G: Fn(&L) -> T,
+    T: PartialEq + Debug,
+{
+    /// Issues a command, recording the event it produces, if any.
+    ///
+    /// Verifies replayability afterwards with [`then_replays`].
+    ///
+    /// [`then_replays`]: Scenario::then_replays
+    #[track_caller]
+    pub fn when(&mut self, command: impl AsRef<[u8]>) -> &mut Self {
+        let event = self.logic.process(command.as_ref());
+        if let Some(event) = &event {
+            self.history.push(event.clone());
+            self.produced.push(event.clone());
+        }
+        self.last = Some(event);
+
+        self.then_replays()
+    }
+
+    /// Asserts that the last command produced `expected`.
+    #[track_caller]
+    pub fn then_event(&mut self, expected: impl AsRef<[u8]>) -> &mut Self {
+        let expected = expected.as_ref();
+        match self.last() {
+            Some(event) if event == expected => {}
+            Some(event) => panic!(
+                "expected event {:?}, produced {:?}",
+                String::from_utf8_lossy(expected),
+                String::from_utf8_lossy(event),
+            ),
+            None => panic!(
+                "expected event {:?}, but the command was rejected",
+                String::from_utf8_lossy(expected),
+            ),
+        }
+        self
+    }
+
+    /// Asserts that the last command was rejected.
+    #[track_caller]
+    pub fn then_rejected(&mut self) -> &mut Self {
+        if let Some(event) = self.last() {
+            panic!(
+                "expected rejection, produced {:?}",
+                String::from_utf8_lossy(event),
+            );
+        }
+        self
+    }
+
+    #[track_caller]
+    fn last(&self) -> Option<&[u8]> {
+        self.last
+            .as_ref()
+            .expect("no command has been issued")
+            .as_deref()
+    }
+}
+
+/// Source of randomness for generating commands in an [`Equivalence`] check.
+pub struct Gen {
+    rng: Rng,
+}
+
+impl Gen {
+    /// Returns a random `u64`.
+    pub fn u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    /// Returns a value in `0..n`, or `0` if `n` is zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.rng.below(n)
+    }
+
+    /// Returns `true` with the given probability.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.rng.chance(probability)
+    }
+
+    /// Returns a random element of `items`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `items` is empty.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.rng.below(items.len() as u64) as usize]
+    }
+}
+
+/// Creates a restarted instance from a snapshot of another.
+type Restore<L> = Box<dyn Fn(&L) -> L>;
+
+/// A property check that consumers replaying the stream reach the same state
+/// as the sequencer that produced it.
+///
+/// Each case generates a random sequence of commands and runs it through
+/// [`Sequencer::process`]. The events produced are replayed into fresh
+/// instances, and state is compared by hash:
+///
+/// - after every command, against a consumer that has stepped every event
+///   produced so far;
+/// - if [`restore`](Equivalence::restore) is configured, against a consumer
+///   restarted from a snapshot at every offset of the stream, which resumes
+///   from the offset returned by [`Logic::load`].
+///
+/// A failing case is shrunk to a minimal command sequence before panicking,
+/// and the report includes the seed needed to reproduce it.
+///
+/// ```ignore
+/// Equivalence::new(Counter::new, Counter::state_hash, |g| {
+///     g.choose(&[&b"inc"[..], b"dec", b"reset"]).to_vec()
+/// })
+/// .restore(|counter| Counter::from_snapshot(counter.snapshot()))
+/// .check();
+/// ```
+pub struct Equivalence<L, F, H, C> {
+    fresh: F,
+    hash: H,
+    generate: C,
+    restore: Option<Restore<L>>,
+    cases: u64,
+    max_commands: u64,
+    seed: u64,
+}
+
+impl<L, F, H, C> Equivalence<L, F, H, C>
+where
+    L: Sequencer,
+    F: Fn() -> L,
+    H: Fn(&L) -> u64,
+    C: Fn(&mut Gen) -> Vec<u8>,
+{
+    /// Creates a check of instances from `fresh`, whose state is summarized
+    /// by `hash`, driven by commands from `generate`.
+    pub fn new(fresh: F, hash: H, generate: C) -> Self {
+        Self {
+            fresh,
+            hash,
+            generate,
+            restore: None,
+            cases: 256,
+            max_commands: 32,
+            seed: 0,
+        }
+    }
+
+    /// Enables restart checks, using `restore` to create a new instance from
+    /// a snapshot of the given one, as a restarted process would.
+    pub fn restore(mut self, restore: impl Fn(&L) -> L + 'static) -> Self {
+        self.restore = Some(Box::new(restore));
+        self
+    }
+
+    /// Sets the number of random cases. Defaults to 256.
+    pub fn cases(mut self, cases: u64) -> Self {
+        self.cases = cases;
+        self
+    }
+
+    /// Sets the maximum number of commands per case. Defaults to 32.
+    pub fn max_commands(mut self, max_commands: u64) -> Self {
+        self.max_commands = max_commands;
+        self
+    }
+
+    /// Sets the seed of the first case. Defaults to 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs every case, panicking with a minimal counterexample on failure.
+    #[track_caller]
+    pub fn check(&self) {
+        for seed in self.seed..self.seed + self.cases {
+            let mut g = Gen {
+                rng: Rng::new(seed),
+            };
+            let count = g.below(self.max_commands + 1);
+            let commands: Vec<_> = (0..count).map(|_| (self.generate)(&mut g)).collect();
+
+            if let Err(failure) = self.run(&commands) {
+                let (commands, failure) = self.shrink(commands, failure);
+                panic!(
+                    "equivalence failed for seed {seed}: {failure}\ncommands: {:?}",
+                    commands
+                        .iter()
+                        .map(|command| String::from_utf8_lossy(command))
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+    }
+
+    /// Runs a command sequence, describing the first divergence found.
+    fn run(&self, commands: &[Vec<u8>]) -> Result<(), String> {
+        let mut sequencer = (self.fresh)();
+        let mut consumer = (self.fresh)();
+        sequencer.load();
+        consumer.load();
+
+        let mut events = Vec::new();
+        for (i, command) in commands.iter().enumerate() {
+            if let Some(event) = sequencer.process(command) {
+                consumer.step(&event);
+                events.push(event);
+            }
+
+            let expected = (self.hash)(&sequencer);
+            let replayed = (self.hash)(&consumer);
+            if replayed != expected {
+                return Err(format!(
+                    "after command {i}, the sequencer hashes to {expected:#x} \
+                     but a consumer replaying {} events hashes to {replayed:#x}",
+                    events.len(),
+                ));
+            }
+        }
+
+        let Some(restore) = &self.restore else {
+            return Ok(());
+        };
+
+        let expected = (self.hash)(&sequencer);
+        for offset in 0..=events.len() {
+            let mut consumer = (self.fresh)();
+            consumer.load();
+            for event in &events[..offset] {
+                consumer.step(event);
+            }
+
+            let mut restarted = restore(&consumer);
+            let resume = restarted.load() as usize;
+            if resume > events.len() {
+                return Err(format!(
+                    "a consumer restored at offset {offset} resumes at offset {resume}, \
+                     past the end of the stream at {}",
+                    events.len(),
+                ));
+            }
+            for event in &events[resume..] {
+                restarted.step(event);
+            }
+
+            let replayed = (self.hash)(&restarted);
+            if replayed != expected {
+                return Err(format!(
+                    "the sequencer hashes to {expected:#x} but a consumer restored at \
+                     offset {offset} and resumed at {resume} hashes to {replayed:#x}",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes runs of commands for as long as the check keeps failing.
+    fn shrink(&self, mut commands: Vec<Vec<u8>>, mut failure: String) -> (Vec<Vec<u8>>, String) {
+        let mut chunk = commands.len().div_ceil(2);
+        while chunk > 0 {
+            let mut start = 0;
+            let mut shrunk = false;
+            while start < commands.len() {
+                let mut candidate = commands.clone();
+                candidate.drain(start..(start + chunk).min(commands.len()));
+
+                match self.run(&candidate) {
+                    Err(reason) => {
+                        commands = candidate;
+                        failure = reason;
+                        shrunk = true;
+                    }
+                    Ok(()) => start += chunk,
+                }
+            }
+            if !shrunk {
+                chunk /= 2;
+            }
+        }
+        (commands, failure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::sequencer::EventGenerator;
+
+    /// A counter that refuses to go below zero.
+    ///
+    /// With `skew` set, the sequencer counts each increment twice while the
+    /// events it produces count once, so replaying them diverges.
+    struct Counter {
+        value: u64,
+        /// Number of events stepped, from which a restored instance resumes.
+        applied: u64,
+        skew: bool,
+    }
+
+    impl Counter {
+        fn new() -> Self {
+            Self {
+                value: 0,
+                applied: 0,
+                skew: false,
+            }
+        }
+
+        fn skewed() -> Self {
+            Self {
+                value: 0,
+                applied: 0,
+                skew: true,
+            }
+        }
+
+        fn command(g: &mut Gen) -> Vec<u8> {
+            g.choose(&[&b"inc"[..], b"dec", b"noop"]).to_vec()
+        }
+    }
+
+    impl Logic for Counter {
+        fn load(&mut self) -> u64 {
+            self.applied
+        }
+
+        fn step(&mut self, event: &[u8]) -> bool {
+            self.applied += 1;
+            match event {
+                b"inc" => self.value += 1,
+                b"dec" => self.value -= 1,
+                _ => {}
+            }
+            true
+        }
+
+        fn caught_up(&mut self) -> bool {
+            true
+        }
+    }
+
+    impl Sequencer for Counter {
+        fn process(&mut self, command: &[u8]) -> Option<Vec<u8>> {
+            match command {
+                b"inc" => self.value += if self.skew { 2 } else { 1 },
+                b"dec" if self.value > 0 => self.value -= 1,
+                _ => return None,
+            }
+            Some(command.to_vec())
+        }
+
+        fn activator(&self) -> Box<dyn EventGenerator> {
+            Box::new(|| b"activate".to_vec())
+        }
+
+        fn heartbeat(&self) -> Box<dyn EventGenerator> {
+            Box::new(|| b"heartbeat".to_vec())
+        }
+
+        fn is_activation(&self, event: &[u8]) -> bool {
+            event == b"activate"
+        }
+    }
+
+    #[test]
+    fn scenario_passes_for_replayable_logic() {
+        let mut scenario = Scenario::new(Counter::new, |counter| counter.value);
+        scenario
+            .given([b"inc"])
+            .when(b"inc")
+            .then_event(b"inc")
+            .when(b"dec")
+            .then_event(b"dec")
+            .when(b"dec")
+            .then_event(b"dec")
+            .when(b"dec")
+            .then_rejected()
+            .then_state(0);
+        assert_eq!(scenario.produced().len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected rejection")]
+    fn scenario_reports_unexpected_event() {
+        Scenario::new(Counter::new, |counter| counter.value)
+            .when(b"inc")
+            .then_rejected();
+    }
+
+    #[test]
+    #[should_panic(expected = "replaying 1 events reached 1, but the sequencer holds 2")]
+    fn scenario_detects_divergent_replay() {
+        Scenario::new(Counter::skewed, |counter| counter.value).when(b"inc");
+    }
+
+    #[test]
+    fn equivalence_passes_for_replayable_logic() {
+        Equivalence::new(Counter::new, |counter| counter.value, Counter::command)
+            .restore(|counter| Counter {
+                value: counter.value,
+                applied: counter.applied,
+                skew: false,
+            })
+            .cases(64)
+            .check();
+    }
+
+    #[test]
+    #[should_panic(expected = "commands: [\"inc\"]")]
+    fn equivalence_shrinks_divergent_logic() {
+        Equivalence::new(Counter::skewed, |counter| counter.value, Counter::command).check();
+    }
+
+    #[test]
+    #[should_panic(expected = "a consumer restored at offset")]
+    fn equivalence_detects_faulty_restore() {
+        Equivalence::new(Counter::new, |counter| counter.value, Counter::command)
+            .restore(|counter| Counter {
+                value: counter.value,
+                applied: 0,
+                skew: false,
+            })
+            .check();
+    }
+}
@@ -0,0 +1,38 @@
+//! Runs the conformance checks against the file backends.
+#![cfg(feature = "conformance")]
+
+use evcore::{
+    conformance,
+    file::{FileElection, FileStream},
+};
+
+use std::{env, fs, path::PathBuf, process, time::Duration};
+
+/// A path in the temporary directory not used by any earlier run.
+fn fresh_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("evcore-conformance-{name}-{}", process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn file_stream_conforms() {
+    let path = fresh_path("stream");
+    conformance::stream(
+        || {
+            let stream = FileStream::open(&path).unwrap();
+            let producer = stream.producer();
+            (stream, producer)
+        },
+        0,
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn file_election_conforms() {
+    let path = fresh_path("election");
+    let lease = Duration::from_millis(200);
+    conformance::election(|| FileElection::new(&path, lease).unwrap(), lease);
+    fs::remove_file(&path).unwrap();
+}
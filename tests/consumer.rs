@@ -0,0 +1,84 @@
+//! Runs a consumer against the file stream.
+
+use evcore::{
+    consumer::{ConsumerBuilder, Liveness},
+    file::FileStream,
+    logic::Logic,
+};
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// Records events until it reads `stop`.
+#[derive(Default)]
+struct Recorder {
+    events: Vec<Vec<u8>>,
+}
+
+impl Logic for Recorder {
+    fn load(&mut self) -> u64 {
+        0
+    }
+
+    fn step(&mut self, event: &[u8]) -> bool {
+        self.events.push(event.to_vec());
+        event != b"stop"
+    }
+
+    fn caught_up(&mut self) -> bool {
+        true
+    }
+}
+
+/// A path in the temporary directory not used by any earlier run.
+fn fresh_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("evcore-consumer-{name}-{}", process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn watchdog_reports_stale_and_live_leader() {
+    let path = fresh_path("watchdog");
+    let stream = FileStream::open(&path).unwrap();
+    let window = Duration::from_millis(300);
+    let (report, reported) = mpsc::channel();
+    let mut logic = Recorder::default();
+
+    thread::scope(|s| {
+        let run = s.spawn(|| {
+            ConsumerBuilder::new()
+                .watchdog(window, move |liveness| report.send(liveness).unwrap())
+                .run(&stream, &mut logic)
+        });
+
+        match reported.recv_timeout(Duration::from_secs(5)).unwrap() {
+            Liveness::Stale { silence } => assert!(silence >= window),
+            Liveness::Live => panic!("reported live before going stale"),
+        }
+
+        stream.append(b"event").unwrap();
+        assert_eq!(
+            reported.recv_timeout(Duration::from_secs(5)).unwrap(),
+            Liveness::Live
+        );
+
+        stream.append(b"stop").unwrap();
+        run.join().unwrap().unwrap();
+    });
+
+    // The watcher thread ended with the run, dropping its sender.
+    assert_eq!(
+        reported.recv_timeout(Duration::from_secs(1)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+    assert_eq!(logic.events, [&b"event"[..], b"stop"]);
+
+    let _ = fs::remove_file(&path);
+}
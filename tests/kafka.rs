@@ -0,0 +1,36 @@
+//! Runs the conformance checks against a Kafka cluster.
+//!
+//! Ignored by default. Run with `EVCORE_TEST_KAFKA_BROKERS` set, such as
+//! `EVCORE_TEST_KAFKA_BROKERS=127.0.0.1:9092 cargo test --features kafka,conformance -- --ignored`.
+//! The checks need an empty topic: either the brokers auto-create topics, or
+//! `EVCORE_TEST_KAFKA_TOPIC` names an existing empty one.
+#![cfg(all(feature = "kafka", feature = "conformance"))]
+
+use evcore::{conformance, kafka::KafkaStream};
+
+use std::{env, time::SystemTime};
+
+#[test]
+#[ignore = "needs a Kafka cluster at EVCORE_TEST_KAFKA_BROKERS"]
+fn stream_conforms() {
+    let Ok(brokers) = env::var("EVCORE_TEST_KAFKA_BROKERS") else {
+        eprintln!("EVCORE_TEST_KAFKA_BROKERS is not set; skipping");
+        return;
+    };
+    let topic = env::var("EVCORE_TEST_KAFKA_TOPIC").unwrap_or_else(|_| {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        format!("evcore-test-{nanos}")
+    });
+
+    conformance::stream(
+        || {
+            let stream = KafkaStream::new(&brokers, &topic);
+            let producer = stream.producer().unwrap();
+            (stream, producer)
+        },
+        0,
+    );
+}
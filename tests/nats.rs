@@ -0,0 +1,35 @@
+//! Runs the conformance checks against a NATS server with JetStream enabled.
+//!
+//! Ignored by default. Run with `EVCORE_TEST_NATS_URL` set, such as
+//! `EVCORE_TEST_NATS_URL=nats://127.0.0.1:4222 cargo test --features nats,conformance -- --ignored`.
+#![cfg(all(feature = "nats", feature = "conformance"))]
+
+use evcore::{conformance, nats::NatsStream};
+
+use std::{env, time::SystemTime};
+
+#[test]
+#[ignore = "needs a NATS server at EVCORE_TEST_NATS_URL"]
+fn stream_conforms() {
+    let Ok(url) = env::var("EVCORE_TEST_NATS_URL") else {
+        eprintln!("EVCORE_TEST_NATS_URL is not set; skipping");
+        return;
+    };
+
+    // A stream and subject not used by any earlier run.
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let name = format!("evcore-test-{nanos}");
+    let subject = format!("evcore.test.{nanos}");
+
+    conformance::stream(
+        || {
+            let stream = NatsStream::connect(&url, &name, &subject).unwrap();
+            let producer = stream.producer();
+            (stream, producer)
+        },
+        0,
+    );
+}
@@ -0,0 +1,58 @@
+//! Runs the conformance checks against a PostgreSQL server.
+//!
+//! Ignored by default. Run with `EVCORE_TEST_POSTGRES` set to a connection
+//! string, such as `EVCORE_TEST_POSTGRES="host=127.0.0.1 user=postgres" cargo
+//! test --features postgres,conformance -- --ignored`.
+#![cfg(all(feature = "postgres", feature = "conformance"))]
+
+use evcore::{
+    conformance,
+    postgres::{PostgresElection, PostgresStream},
+};
+
+use std::{
+    env,
+    time::{Duration, SystemTime},
+};
+
+fn params() -> Option<String> {
+    let params = env::var("EVCORE_TEST_POSTGRES").ok();
+    if params.is_none() {
+        eprintln!("EVCORE_TEST_POSTGRES is not set; skipping");
+    }
+    params
+}
+
+fn nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL server at EVCORE_TEST_POSTGRES"]
+fn stream_conforms() {
+    let Some(params) = params() else { return };
+    // Mixed case checks that the table name is quoted consistently.
+    let table = format!("Evcore_Test_{}", nanos());
+    conformance::stream(
+        || {
+            let stream = PostgresStream::new(&params, &table).unwrap();
+            let producer = stream.producer();
+            (stream, producer)
+        },
+        1,
+    );
+}
+
+#[test]
+#[ignore = "needs a PostgreSQL server at EVCORE_TEST_POSTGRES"]
+fn election_conforms() {
+    let Some(params) = params() else { return };
+    let key = nanos() as i64;
+    conformance::election(
+        || PostgresElection::new(&params, key),
+        Duration::from_millis(500),
+    );
+}
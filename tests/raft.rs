@@ -0,0 +1,146 @@
+//! Runs a three-node Raft group on localhost.
+#![cfg(feature = "raft")]
+
+use evcore::{
+    Receiver,
+    election::{Election, Leader},
+    raft::{RaftConfig, RaftNode},
+    stream::{Producer, Stream},
+};
+
+use std::{
+    env, fs,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    process, thread,
+    time::{Duration, Instant},
+};
+
+/// Addresses of `count` free localhost ports.
+fn addresses(count: usize) -> Vec<SocketAddr> {
+    let listeners: Vec<_> = (0..count)
+        .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+        .collect();
+    listeners.iter().map(|l| l.local_addr().unwrap()).collect()
+}
+
+fn data_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("evcore-raft-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Waits until exactly one node leads, returning its index.
+fn leader(nodes: &[RaftNode]) -> usize {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let leaders: Vec<_> = (0..nodes.len()).filter(|&i| nodes[i].is_leader()).collect();
+        if let [leader] = leaders[..] {
+            return leader;
+        }
+        assert!(Instant::now() < deadline, "no leader elected");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn three_nodes_elect_and_replicate() {
+    let peers = addresses(3);
+    let dir = data_dir("group");
+    let nodes: Vec<_> = (0..3)
+        .map(|id| {
+            RaftNode::start(RaftConfig::new(id, peers.clone(), dir.join(id.to_string()))).unwrap()
+        })
+        .collect();
+
+    let leader = leader(&nodes);
+    let events: Vec<_> = (0..20).map(|i| format!("event {i}").into_bytes()).collect();
+    for event in &events {
+        nodes[leader].publish(event);
+    }
+
+    for node in &nodes {
+        let receiver = node.subscribe(0);
+        for event in &events {
+            assert_eq!(receiver.recv(), *event);
+        }
+    }
+    assert_eq!(nodes.iter().filter(|node| node.is_leader()).count(), 1);
+
+    drop(nodes);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn leader_is_reported_and_resigns() {
+    let peers = addresses(3);
+    let dir = data_dir("resign");
+    let nodes: Vec<_> = (0..3)
+        .map(|id| {
+            RaftNode::start(RaftConfig::new(id, peers.clone(), dir.join(id.to_string()))).unwrap()
+        })
+        .collect();
+    assert_eq!(nodes[0].lease(), Some(Duration::from_millis(150)));
+
+    let first = leader(&nodes);
+    let expected = Leader {
+        id: first.to_string(),
+        address: Some(peers[first].to_string()),
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while nodes
+        .iter()
+        .any(|node| node.leader().as_ref() != Some(&expected))
+    {
+        assert!(
+            Instant::now() < deadline,
+            "leader not reported by every node"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    nodes[first].resign();
+    assert!(!nodes[first].is_leader());
+    assert!(!nodes[first].renew());
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let leaders: Vec<_> = (0..nodes.len()).filter(|&i| nodes[i].is_leader()).collect();
+        if let [next] = leaders[..]
+            && next != first
+        {
+            break;
+        }
+        assert!(Instant::now() < deadline, "no other node took over");
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    drop(nodes);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(feature = "conformance")]
+#[test]
+fn stream_conforms_across_terms() {
+    use evcore::conformance::{self, Cursor};
+
+    let peers = addresses(3);
+    let dir = data_dir("conformance");
+    let nodes: Vec<_> = (0..3)
+        .map(|id| {
+            RaftNode::start(RaftConfig::new(id, peers.clone(), dir.join(id.to_string()))).unwrap()
+        })
+        .collect();
+
+    // Each term opens with a no-op entry, leaving a gap in the offsets of
+    // delivered events.
+    let first = leader(&nodes);
+    let cursor = conformance::ordered_delivery(&nodes[first], &nodes[first], &Cursor::new(0));
+    nodes[first].resign();
+    let next = leader(&nodes);
+    let cursor = conformance::replay_from_offset(&nodes[next], &nodes[next], &cursor);
+    conformance::multiple_subscribers(&nodes[next], &nodes[next], &cursor);
+
+    drop(nodes);
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,56 @@
+//! Runs the conformance checks against a Redis server.
+//!
+//! Ignored by default. Run with `EVCORE_TEST_REDIS_URL` set, such as
+//! `EVCORE_TEST_REDIS_URL=redis://127.0.0.1/ cargo test --features redis,conformance -- --ignored`.
+#![cfg(all(feature = "redis", feature = "conformance"))]
+
+use evcore::{
+    conformance,
+    redis::{RedisElection, RedisStream},
+};
+
+use std::{
+    env,
+    time::{Duration, SystemTime},
+};
+
+fn url() -> Option<String> {
+    let url = env::var("EVCORE_TEST_REDIS_URL").ok();
+    if url.is_none() {
+        eprintln!("EVCORE_TEST_REDIS_URL is not set; skipping");
+    }
+    url
+}
+
+/// A key not used by any earlier run.
+fn key(name: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    format!("evcore-test-{name}-{nanos}")
+}
+
+#[test]
+#[ignore = "needs a Redis server at EVCORE_TEST_REDIS_URL"]
+fn stream_conforms() {
+    let Some(url) = url() else { return };
+    let key = key("stream");
+    conformance::stream(
+        || {
+            let stream = RedisStream::new(&url, &key).unwrap();
+            let producer = stream.producer();
+            (stream, producer)
+        },
+        0,
+    );
+}
+
+#[test]
+#[ignore = "needs a Redis server at EVCORE_TEST_REDIS_URL"]
+fn election_conforms() {
+    let Some(url) = url() else { return };
+    let key = key("election");
+    let lease = Duration::from_millis(500);
+    conformance::election(|| RedisElection::new(&url, &key, lease).unwrap(), lease);
+}
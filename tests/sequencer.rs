@@ -0,0 +1,337 @@
+//! Runs a sequencer against the file backends and an in-memory inbox.
+
+use evcore::{
+    Receiver, Sender, Sequencer,
+    election::Election,
+    file::{FileElection, FileStream},
+    inbox::Inbox,
+    logic::Logic,
+    sequencer::{CatchUp, EventGenerator, RunError, SequencerBuilder},
+};
+
+use std::{
+    collections::VecDeque,
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+/// An inbox backed by a queue in memory.
+#[derive(Default)]
+struct Queue {
+    commands: Mutex<VecDeque<Vec<u8>>>,
+    pushed: Condvar,
+}
+
+impl Receiver for Queue {
+    fn recv(&self) -> Vec<u8> {
+        let commands = self.commands.lock().unwrap();
+        let mut commands = self
+            .pushed
+            .wait_while(commands, |commands| commands.is_empty())
+            .unwrap();
+        commands.pop_front().unwrap()
+    }
+}
+
+impl Sender for Queue {
+    fn send(&self, command: &[u8]) {
+        self.commands.lock().unwrap().push_back(command.to_vec());
+        self.pushed.notify_all();
+    }
+}
+
+impl Inbox for Queue {
+    fn clear(&self) {
+        self.commands.lock().unwrap().clear();
+    }
+
+    fn depth(&self) -> Option<usize> {
+        Some(self.commands.lock().unwrap().len())
+    }
+}
+
+/// Counts events, and records its count in `checksum <count>` events.
+struct Counter {
+    name: &'static str,
+    count: u64,
+}
+
+impl Counter {
+    fn new(name: &'static str) -> Self {
+        Self { name, count: 0 }
+    }
+}
+
+impl Logic for Counter {
+    fn load(&mut self) -> u64 {
+        0
+    }
+
+    fn step(&mut self, event: &[u8]) -> bool {
+        if self.read_checksum(event).is_none() {
+            self.count += 1;
+        }
+        true
+    }
+
+    fn caught_up(&mut self) -> bool {
+        false
+    }
+
+    fn state_hash(&self) -> Option<u64> {
+        Some(self.count)
+    }
+
+    fn read_checksum(&self, event: &[u8]) -> Option<u64> {
+        std::str::from_utf8(event)
+            .ok()?
+            .strip_prefix("checksum ")?
+            .parse()
+            .ok()
+    }
+}
+
+impl Sequencer for Counter {
+    fn process(&mut self, command: &[u8]) -> Option<Vec<u8>> {
+        (command != b"heartbeat").then(|| command.to_vec())
+    }
+
+    fn activator(&self) -> Box<dyn EventGenerator> {
+        let event = format!("activate {}", self.name).into_bytes();
+        Box::new(move || event.clone())
+    }
+
+    fn heartbeat(&self) -> Box<dyn EventGenerator> {
+        Box::new(|| b"heartbeat".to_vec())
+    }
+
+    fn is_activation(&self, event: &[u8]) -> bool {
+        *event == *format!("activate {}", self.name).as_bytes()
+    }
+}
+
+/// A path in the temporary directory not used by any earlier run.
+fn fresh_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("evcore-sequencer-{name}-{}", process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn divergence_stops_the_run() {
+    let (stream_path, lock_path) = (fresh_path("diverged-stream"), fresh_path("diverged-lock"));
+    let stream = FileStream::open(&stream_path).unwrap();
+    stream.append(b"first").unwrap();
+    stream.append(b"checksum 2").unwrap();
+    let lease = Duration::from_millis(500);
+    let election = FileElection::new(&lock_path, lease).unwrap();
+
+    let result = SequencerBuilder::new()
+        .catch_up(CatchUp::Idle(Duration::from_millis(100)))
+        .run(
+            &stream,
+            &stream.producer(),
+            &Queue::default(),
+            &election,
+            Counter::new("a"),
+        );
+
+    match result {
+        Err(RunError::Diverged(divergence)) => {
+            assert_eq!((divergence.offset, divergence.expected), (1, 2));
+            assert_eq!(divergence.actual, 1);
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("diverged run returned its logic"),
+    }
+    assert!(FileElection::new(&lock_path, lease).unwrap().elect());
+
+    let _ = fs::remove_file(&stream_path);
+    let _ = fs::remove_file(&lock_path);
+}
+
+#[test]
+fn liveness_window_requires_stream_heartbeats() {
+    let (stream_path, lock_path) = (fresh_path("liveness-stream"), fresh_path("liveness-lock"));
+    let stream = FileStream::open(&stream_path).unwrap();
+    let election = FileElection::new(&lock_path, Duration::from_millis(500)).unwrap();
+
+    let result = SequencerBuilder::new()
+        .liveness_window(Duration::from_secs(1))
+        .run(
+            &stream,
+            &stream.producer(),
+            &Queue::default(),
+            &election,
+            Counter::new("a"),
+        );
+
+    match result {
+        Err(RunError::InvalidOption(invalid)) => assert_eq!(invalid.option, "liveness_window"),
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("ran without stream heartbeats"),
+    }
+    assert!(stream.is_empty());
+
+    let _ = fs::remove_file(&stream_path);
+    let _ = fs::remove_file(&lock_path);
+}
+
+#[test]
+fn lease_options_must_fit_the_lease() {
+    let (stream_path, lock_path) = (fresh_path("lease-stream"), fresh_path("lease-lock"));
+    let stream = FileStream::open(&stream_path).unwrap();
+    let election = FileElection::new(&lock_path, Duration::from_millis(500)).unwrap();
+
+    for (builder, option) in [
+        (
+            SequencerBuilder::new().lease_margin(Duration::from_millis(500)),
+            "lease_margin",
+        ),
+        (
+            SequencerBuilder::new()
+                .lease_margin(Duration::from_millis(200))
+                .renew_interval(Duration::from_millis(300)),
+            "renew_interval",
+        ),
+    ] {
+        let result = builder.run(
+            &stream,
+            &stream.producer(),
+            &Queue::default(),
+            &election,
+            Counter::new("a"),
+        );
+        match result {
+            Err(RunError::InvalidOption(invalid)) => assert_eq!(invalid.option, option),
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("ran with {option} outside the lease"),
+        }
+    }
+    assert!(stream.is_empty());
+
+    let _ = fs::remove_file(&stream_path);
+    let _ = fs::remove_file(&lock_path);
+}
+
+#[test]
+fn idle_leader_meets_its_progress_deadline() {
+    use evcore::sequencer::{LostLease, SequencerHandle, Status};
+    use std::{thread, time::Instant};
+
+    let (stream_path, lock_path) = (fresh_path("progress-stream"), fresh_path("progress-lock"));
+    let stream = FileStream::open(&stream_path).unwrap();
+    let election = FileElection::new(&lock_path, Duration::from_millis(500)).unwrap();
+    let handle = SequencerHandle::new();
+
+    thread::scope(|s| {
+        let run = s.spawn(|| {
+            // Counter discards the heartbeats, so the loop publishes nothing.
+            SequencerBuilder::new()
+                .catch_up(CatchUp::Idle(Duration::from_millis(100)))
+                .renew_interval(Duration::from_millis(50))
+                .progress_deadline(Duration::from_millis(200))
+                .lost_lease(LostLease::Stop)
+                .handle(handle.clone())
+                .run(
+                    &stream,
+                    &stream.producer(),
+                    &Queue::default(),
+                    &election,
+                    Counter::new("a"),
+                )
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while handle.status() != Status::Activated {
+            assert!(Instant::now() < deadline, "sequencer never activated");
+            thread::sleep(Duration::from_millis(10));
+        }
+        thread::sleep(Duration::from_secs(1));
+        assert_eq!(handle.status(), Status::Activated);
+
+        handle.step_down();
+        run.join().unwrap().unwrap();
+    });
+
+    let _ = fs::remove_file(&stream_path);
+    let _ = fs::remove_file(&lock_path);
+}
+
+#[cfg(feature = "health")]
+#[test]
+fn step_down_stops_and_is_not_ready() {
+    use evcore::{
+        health::Health,
+        sequencer::{SequencerHandle, Status},
+    };
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+        time::Instant,
+    };
+
+    fn get(addr: &str, path: &str) -> String {
+        let mut conn = TcpStream::connect(addr).unwrap();
+        write!(conn, "GET {path} HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        conn.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    let (stream_path, lock_path) = (fresh_path("step-down-stream"), fresh_path("step-down-lock"));
+    let stream = FileStream::open(&stream_path).unwrap();
+    let election = FileElection::new(&lock_path, Duration::from_millis(500)).unwrap();
+    let (health, handle) = (Health::sequencer(), SequencerHandle::new());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn({
+        let health = health.clone();
+        move || health.serve(listener)
+    });
+
+    thread::scope(|s| {
+        let run = s.spawn(|| {
+            SequencerBuilder::new()
+                .catch_up(CatchUp::Idle(Duration::from_millis(100)))
+                .observer(health.clone())
+                .handle(handle.clone())
+                .run(
+                    &stream,
+                    &stream.producer(),
+                    &Queue::default(),
+                    &election,
+                    Counter::new("a"),
+                )
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while handle.status() != Status::Activated {
+            assert!(Instant::now() < deadline, "sequencer never activated");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(get(&addr, "/ready").starts_with("HTTP/1.1 200"));
+
+        handle.step_down();
+        run.join().unwrap().unwrap();
+    });
+
+    assert_eq!(handle.status(), Status::Stopped);
+    assert_eq!(health.status(), Status::Stopped);
+    assert!(!health.is_ready());
+    assert!(get(&addr, "/ready").starts_with("HTTP/1.1 503"));
+    assert!(get(&addr, "/health").starts_with("HTTP/1.1 503"));
+    assert!(
+        FileElection::new(&lock_path, Duration::from_millis(500))
+            .unwrap()
+            .elect()
+    );
+
+    let _ = fs::remove_file(&stream_path);
+    let _ = fs::remove_file(&lock_path);
+}
@@ -0,0 +1,162 @@
+//! Runs two sequencers through a leader failover under simulation.
+#![cfg(feature = "sim")]
+
+use evcore::{
+    Sender, Sequencer,
+    clock::Clock,
+    logic::Logic,
+    sequencer::{CatchUp, EventGenerator, SequencerBuilder},
+    sim::{Faults, Sim},
+};
+
+use std::time::Duration;
+
+/// Publishes every command other than heartbeats, marking its activation with its name.
+struct Echo {
+    name: &'static str,
+}
+
+impl Logic for Echo {
+    fn load(&mut self) -> u64 {
+        0
+    }
+
+    fn step(&mut self, _event: &[u8]) -> bool {
+        true
+    }
+
+    fn caught_up(&mut self) -> bool {
+        true
+    }
+}
+
+impl Sequencer for Echo {
+    fn process(&mut self, command: &[u8]) -> Option<Vec<u8>> {
+        (command != b"heartbeat").then(|| command.to_vec())
+    }
+
+    fn activator(&self) -> Box<dyn EventGenerator> {
+        let event = format!("activate {}", self.name).into_bytes();
+        Box::new(move || event.clone())
+    }
+
+    fn heartbeat(&self) -> Box<dyn EventGenerator> {
+        Box::new(|| b"heartbeat".to_vec())
+    }
+
+    fn is_activation(&self, event: &[u8]) -> bool {
+        *event == *format!("activate {}", self.name).as_bytes()
+    }
+}
+
+/// Paces every task of the election loop at 100ms, catching up after
+/// 500ms without events.
+fn builder() -> SequencerBuilder {
+    let interval = Duration::from_millis(100);
+    SequencerBuilder::new()
+        .election_interval(interval)
+        .renew_interval(interval)
+        .activation_interval(interval)
+        .heartbeat_interval(interval)
+        .catch_up(CatchUp::LogicOrIdle(Duration::from_millis(500)))
+}
+
+/// Like [`builder`], but stops publishing 400ms before the lease runs out,
+/// so that a publish stalled for up to 400ms still lands within the lease.
+fn stall_tolerant_builder() -> SequencerBuilder {
+    builder().lease_margin(Duration::from_millis(400))
+}
+
+/// Runs sequencers `a` and `b` with a client sending commands to both,
+/// kills the leader, if any, partway through, and returns the published events.
+fn failover(seed: u64, faults: Faults, builder: fn() -> SequencerBuilder) -> Vec<Vec<u8>> {
+    let mut sim = Sim::with_faults(seed, faults);
+    let stream = sim.stream();
+    let election = sim.election(Duration::from_secs(1));
+
+    let mut inboxes = Vec::new();
+    for name in ["a", "b"] {
+        let inbox = sim.inbox();
+        inboxes.push(inbox.clone());
+        sim.sequencer(
+            name,
+            builder(),
+            stream.clone(),
+            stream.producer(),
+            inbox,
+            election.clone(),
+            Echo { name },
+        );
+    }
+
+    let clock = sim.clock();
+    sim.spawn("client", move || {
+        for i in 0.. {
+            for inbox in &inboxes {
+                inbox.send(format!("command {i}").as_bytes());
+            }
+            clock.sleep(Duration::from_millis(50));
+        }
+    });
+
+    sim.run_for(Duration::from_secs(3));
+    // Under faults, both sequencers may already have exited.
+    if let Some(leader) = election.holder() {
+        sim.kill(&leader);
+    }
+    sim.run_for(Duration::from_secs(5));
+
+    stream.assert_exclusive();
+    stream.events()
+}
+
+fn activations(events: &[Vec<u8>]) -> Vec<String> {
+    events
+        .iter()
+        .filter(|event| event.starts_with(b"activate"))
+        .map(|event| String::from_utf8_lossy(event).into_owned())
+        .collect()
+}
+
+#[test]
+fn failover_is_exclusive() {
+    for seed in 0..8 {
+        let events = failover(seed, Faults::default(), builder);
+        let activations = activations(&events);
+        assert_eq!(activations.len(), 2, "seed {seed}: {activations:?}");
+        assert_ne!(activations[0], activations[1], "seed {seed}");
+    }
+}
+
+#[test]
+fn failover_under_partitions_is_exclusive() {
+    let faults = Faults {
+        partition: 0.05,
+        partition_duration: Duration::from_millis(1500),
+        ..Faults::default()
+    };
+    for seed in 0..8 {
+        failover(seed, faults.clone(), builder);
+    }
+}
+
+#[test]
+fn failover_under_stalls_and_delayed_renewals_is_exclusive() {
+    let faults = Faults {
+        publish_stall: 0.2,
+        max_publish_stall: Duration::from_millis(400),
+        lease_expiry: 0.05,
+        ..Faults::default()
+    };
+    for seed in 0..32 {
+        failover(seed, faults.clone(), stall_tolerant_builder);
+    }
+}
+
+#[test]
+fn failover_replays_identically() {
+    assert_eq!(
+        failover(7, Faults::default(), builder),
+        failover(7, Faults::default(), builder)
+    );
+}